@@ -0,0 +1,390 @@
+//! Measures the gas used by every method of the actor, at several registry
+//! sizes and HAMT bit widths, and prints a table per actor build.
+//!
+//! ```shell
+//! cargo build
+//! cargo bench --features client --bench gas -- --sizes 10,100,1000 --bit-widths 3,5,8
+//! ```
+//!
+//! Builds are compared with `--wasm <LABEL>=<PATH>`, repeated. The default
+//! is the debug build.
+//!
+//! The tester only creates machines at epoch 0, methods that wait for
+//! epochs to pass are measured after re-instantiating the machine at a
+//! later epoch on the same state tree.
+
+use anyhow::{anyhow, Result};
+use cid::multihash::Multihash;
+use cid::Cid;
+use fvm::executor::{ApplyKind, ApplyRet, DefaultExecutor, Executor};
+use fvm::machine::{DefaultMachine, Engine, Machine};
+use fvm_integration_tests::dummy::DummyExterns;
+use fvm_integration_tests::tester::{Account, Tester};
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::bigint::Zero;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::message::Message;
+use fvm_shared::state::StateTreeVersion;
+use fvm_shared::version::NetworkVersion;
+use fvm_shared::ActorID;
+use std::collections::HashMap;
+use std::rc::Rc;
+use uptime_checker::client::{
+    AssignmentsPage, InitParams, Method, NodeEntry, NodeKind, Params, ParamsBuilder, PeerID, Quorum, VotingMode,
+    DEFAULT_HAMT_BIT_WIDTH, DEFAULT_NAMESPACE,
+};
+
+const DEFAULT_WASM: &str = "target/debug/wbuild/uptime_checker/uptime_checker.compact.wasm";
+const DEFAULT_SIZES: &[u64] = &[10, 100, 1000];
+const DEFAULT_BIT_WIDTHS: &[u32] = &[DEFAULT_HAMT_BIT_WIDTH];
+const ACTOR_ID: ActorID = 10000;
+const SYSTEM_ACTOR_ID: ActorID = 0;
+const GAS_LIMIT: i64 = 10_000_000_000;
+const ADDRESS: &str = "/ip4/10.0.0.1/tcp/4001";
+/// Members registered per `register_members_batch` call
+const BATCH_SIZE: u64 = 10;
+/// Phases of the commit-reveal namespace, in epochs
+const COMMIT_DURATION: ChainEpoch = 10;
+const REVEAL_DURATION: ChainEpoch = 10;
+const APPEAL_WINDOW: ChainEpoch = 10;
+const JAIL_COOLDOWN: ChainEpoch = 10;
+
+/// A libp2p-key CID encoded PeerID, unique per `i`, inlining a fake
+/// ed25519 public key.
+fn peer_id(i: u64) -> PeerID {
+    let mut key = vec![0x08, 0x01, 0x12, 0x20];
+    key.extend_from_slice(&[0u8; 24]);
+    key.extend_from_slice(&i.to_be_bytes());
+    Cid::new_v1(0x72, Multihash::wrap(0x00, &key).unwrap()).to_string()
+}
+
+/// An actor deployed in a fresh machine, with `size` checkers and members
+/// owned by `owner`, in HAMTs of `bit_width`.
+struct Registry {
+    tester: Tester<Rc<MemoryBlockstore>>,
+    /// The blockstore of the tester, kept to re-instantiate the machine
+    store: Rc<MemoryBlockstore>,
+    owner: Account,
+    other: Account,
+    /// Creators of the other checkers of the commit-reveal namespace
+    voters: [Account; 2],
+    actor: Address,
+    sequences: HashMap<ActorID, u64>,
+    size: u64,
+    bit_width: u32,
+}
+
+impl Registry {
+    fn new(wasm: &[u8], size: u64, bit_width: u32) -> Result<(Self, u64)> {
+        let store = Rc::new(MemoryBlockstore::default());
+        let mut tester = Tester::new(NetworkVersion::V15, StateTreeVersion::V4, store.clone())?;
+        let [owner, other, first, second]: [Account; 4] = tester.create_accounts()?;
+
+        // the constructor writes the state, any placeholder will do
+        let placeholder = tester.set_state(&())?;
+        let actor = Address::new_id(ACTOR_ID);
+        tester.set_actor_from_bin(wasm, placeholder, actor, TokenAmount::zero())?;
+        tester.instantiate_machine()?;
+
+        let mut r = Self {
+            tester,
+            store,
+            owner,
+            other,
+            voters: [first, second],
+            actor,
+            sequences: HashMap::new(),
+            size,
+            bit_width,
+        };
+        let init = ParamsBuilder::new(DEFAULT_NAMESPACE).init(InitParams {
+            ids: vec![peer_id(0)],
+            creators: vec![r.owner.1],
+            addresses: vec![vec![ADDRESS.to_string()]],
+            checkers_per_member: Some(1),
+            // evictions are immediate and jails short, the appeal window
+            // and the cooldown are measured in the commit-reveal namespace
+            appeal_window: Some(0),
+            jail_cooldown: Some(0),
+            hamt_bit_width: Some(bit_width),
+            ..Default::default()
+        })?;
+        let init_gas = r.apply(SYSTEM_ACTOR_ID, init, ApplyKind::Implicit)?.msg_receipt.gas_used as u64;
+
+        let b = r.builder();
+        for i in 1..size {
+            r.send_owner(b.new_checker(peer_id(i), vec![ADDRESS.to_string()], vec![])?)?;
+        }
+        for i in (0..size).step_by(BATCH_SIZE as usize) {
+            let members = (i..(i + BATCH_SIZE).min(size))
+                .map(|j| NodeEntry { id: peer_id(size + j), addresses: vec![ADDRESS.to_string()], health_checks: vec![] })
+                .collect();
+            r.send_owner(b.register_members_batch(members)?)?;
+        }
+        Ok((r, init_gas))
+    }
+
+    fn builder(&self) -> ParamsBuilder {
+        ParamsBuilder::new(DEFAULT_NAMESPACE)
+    }
+
+    fn apply(&mut self, from: ActorID, params: Params, kind: ApplyKind) -> Result<ApplyRet> {
+        let sequence = self.sequences.entry(from).or_default();
+        let message = Message {
+            version: 0,
+            from: Address::new_id(from),
+            to: self.actor,
+            sequence: *sequence,
+            value: TokenAmount::zero(),
+            method_num: params.method.num(),
+            params: RawBytes::new(params.bytes),
+            gas_limit: GAS_LIMIT,
+            gas_fee_cap: TokenAmount::zero(),
+            gas_premium: TokenAmount::zero(),
+        };
+        *sequence += 1;
+
+        let ret = self
+            .tester
+            .executor
+            .as_mut()
+            .ok_or_else(|| anyhow!("machine not instantiated"))?
+            .execute_message(message, kind, 100)?;
+        if !ret.msg_receipt.exit_code.is_success() {
+            return Err(anyhow!(
+                "{} failed with {}: {:?}",
+                params.method.name(),
+                ret.msg_receipt.exit_code,
+                ret.failure_info
+            ));
+        }
+        Ok(ret)
+    }
+
+    fn send(&mut self, from: ActorID, params: Params) -> Result<u64> {
+        Ok(self.apply(from, params, ApplyKind::Explicit)?.msg_receipt.gas_used as u64)
+    }
+
+    fn send_owner(&mut self, params: Params) -> Result<u64> {
+        self.send(self.owner.0, params)
+    }
+
+    /// Re-instantiates the machine at `epoch`, on the state tree left by
+    /// the current one.
+    fn advance(&mut self, epoch: ChainEpoch) -> Result<()> {
+        let executor = self.tester.executor.as_mut().ok_or_else(|| anyhow!("machine not instantiated"))?;
+        let root = executor.flush()?;
+        let context = executor.context();
+        let mut mc = context.network.for_epoch(epoch, root);
+        mc.set_base_fee(context.base_fee.clone());
+
+        let engine = Engine::new_default((&mc.network).into())?;
+        let machine = DefaultMachine::new(&engine, &mc, self.store.clone(), DummyExterns)?;
+        self.tester.executor = Some(DefaultExecutor::new(machine));
+        Ok(())
+    }
+
+    /// Calls every method once, returning the gas used by each. The voting
+    /// rounds, appeals and jails are measured on a separate commit-reveal
+    /// namespace of four checkers, moving the machine through the epochs
+    /// of each phase.
+    fn run(&mut self) -> Result<HashMap<Method, u64>> {
+        let b = self.builder();
+        let n = self.size;
+        let (checker, member, new_checker, new_member) = (peer_id(1), peer_id(n), peer_id(2 * n), peer_id(2 * n + 1));
+        let addresses = || vec![ADDRESS.to_string()];
+        let mut gas = HashMap::new();
+
+        gas.insert(Method::NewChecker, self.send_owner(b.new_checker(new_checker.clone(), addresses(), vec![])?)?);
+        gas.insert(Method::NewMember, self.send_owner(b.new_member(new_member.clone(), addresses(), vec![])?)?);
+        gas.insert(Method::EditChecker, self.send_owner(b.edit_checker(checker.clone(), addresses(), vec![])?)?);
+        gas.insert(Method::EditMember, self.send_owner(b.edit_member(member.clone(), addresses(), vec![])?)?);
+        gas.insert(Method::RmChecker, self.send_owner(b.rm_checker(new_checker)?)?);
+        gas.insert(Method::RmMember, self.send_owner(b.rm_member(new_member)?)?);
+
+        // the owner created the initial checker, so it is an admin
+        gas.insert(Method::Subscribe, self.send_owner(b.subscribe(self.other.1, 2)?)?);
+
+        gas.insert(
+            Method::TransferOwnership,
+            self.send_owner(b.transfer_ownership(NodeKind::Member, member.clone(), self.other.1, true)?)?,
+        );
+        gas.insert(Method::AcceptOwnership, self.send(self.other.0, b.accept_ownership(NodeKind::Member, member.clone())?)?);
+
+        // the first, full, page is measured, the others are only
+        // scanned for a member assigned to `checker`
+        let mut offset = Some(0);
+        let mut reported = None;
+        while let (Some(o), None) = (offset, &reported) {
+            let ret = self.apply(self.owner.0, b.get_assignments(checker.clone(), 0, o, None)?, ApplyKind::Explicit)?;
+            gas.entry(Method::GetAssignments).or_insert(ret.msg_receipt.gas_used as u64);
+            let page: AssignmentsPage = ret.msg_receipt.return_data.deserialize()?;
+            reported = page.members.into_iter().next();
+            offset = page.next;
+        }
+        if let Some(reported) = reported {
+            gas.insert(Method::ReportMember, self.send_owner(b.report_member(reported, checker.clone())?)?);
+        }
+
+        gas.insert(Method::MemberStatus, self.send_owner(b.member_status(member.clone())?)?);
+        gas.insert(Method::MemberUptime, self.send_owner(b.member_uptime(member, 100)?)?);
+        gas.insert(Method::CheckerStatus, self.send_owner(b.checker_status(checker.clone())?)?);
+
+        // a single vote stays below the quorum unless the registry is tiny
+        gas.insert(Method::ReportChecker, self.send_owner(b.report_checker(peer_id(n - 1), checker.clone())?)?);
+        let batch = (2..n.min(2 + BATCH_SIZE)).map(peer_id).collect();
+        gas.insert(Method::ReportCheckersBatch, self.send_owner(b.report_checkers_batch(batch, peer_id(0))?)?);
+        // resends the notifications of the checkers jailed above the
+        // subscriber failed to handle, if any
+        gas.insert(Method::DeliverNotifications, self.send(self.other.0, b.deliver_notifications(self.other.1)?)?);
+        gas.insert(Method::Unsubscribe, self.send(self.other.0, b.unsubscribe(self.other.1)?)?);
+        let members = (0..BATCH_SIZE)
+            .map(|i| NodeEntry { id: peer_id(3 * n + i), addresses: addresses(), health_checks: vec![] })
+            .collect();
+        gas.insert(Method::RegisterMembersBatch, self.send_owner(b.register_members_batch(members)?)?);
+
+        gas.extend(self.run_commit_reveal()?);
+
+        gas.insert(Method::Migrate, self.send_owner(ParamsBuilder::migrate())?);
+        Ok(gas)
+    }
+
+    /// Votes the last of four checkers offline in a new commit-reveal
+    /// namespace, with a quorum of a third so that two reveals out of
+    /// three commitments evict it, then jails and releases it.
+    fn run_commit_reveal(&mut self) -> Result<HashMap<Method, u64>> {
+        let ns = ParamsBuilder::new("commit-reveal");
+        let accused = peer_id(3);
+        let addresses = || vec![ADDRESS.to_string()];
+        let [first, second] = self.voters;
+        let mut gas = HashMap::new();
+
+        gas.insert(
+            Method::NewNamespace,
+            self.send_owner(ns.new_namespace(InitParams {
+                ids: (0..4).map(peer_id).collect(),
+                creators: vec![self.owner.1, self.other.1, first.1, second.1],
+                addresses: vec![addresses(); 4],
+                checkers_per_member: Some(1),
+                voting_mode: VotingMode::CommitReveal {
+                    commit_duration: COMMIT_DURATION,
+                    reveal_duration: REVEAL_DURATION,
+                },
+                appeal_window: Some(APPEAL_WINDOW),
+                jail_cooldown: Some(JAIL_COOLDOWN),
+                quorum: Some(Quorum::new(1, 3)?),
+                hamt_bit_width: Some(self.bit_width),
+                ..Default::default()
+            })?)?,
+        );
+
+        let voters = [(self.owner.0, peer_id(0)), (self.other.0, peer_id(1)), (first.0, peer_id(2))];
+        for (i, (from, voter)) in voters.iter().enumerate() {
+            let used = self.send(*from, ns.commit_report(accused.clone(), voter.clone(), true, b"salt")?)?;
+            if i == 0 {
+                gas.insert(Method::CommitReport, used);
+            }
+        }
+
+        // the third commitment is never revealed, so the round waits for
+        // the end of the reveal phase
+        self.advance(COMMIT_DURATION)?;
+        for (i, (from, voter)) in voters.iter().take(2).enumerate() {
+            let used = self.send(*from, ns.reveal_report(accused.clone(), voter.clone(), true, b"salt")?)?;
+            if i == 0 {
+                gas.insert(Method::RevealReport, used);
+            }
+        }
+
+        let evicted = COMMIT_DURATION + REVEAL_DURATION;
+        self.advance(evicted)?;
+        gas.insert(Method::FinalizeReport, self.send_owner(ns.finalize_report(accused.clone())?)?);
+        gas.insert(Method::AppealEviction, self.send(second.0, ns.appeal_eviction(accused.clone())?)?);
+        // a single vouch does not contest the quorum, the checker is jailed
+        gas.insert(Method::VouchOnline, self.send_owner(ns.vouch_online(accused.clone(), peer_id(0))?)?);
+
+        self.advance(evicted + APPEAL_WINDOW)?;
+        gas.insert(Method::FinalizeEviction, self.send_owner(ns.finalize_eviction(accused.clone())?)?);
+
+        self.advance(evicted + APPEAL_WINDOW + JAIL_COOLDOWN)?;
+        gas.insert(Method::Unjail, self.send(second.0, ns.unjail(accused)?)?);
+        Ok(gas)
+    }
+}
+
+struct Options {
+    sizes: Vec<u64>,
+    bit_widths: Vec<u32>,
+    builds: Vec<(String, String)>,
+}
+
+fn options() -> Result<Options> {
+    let mut sizes = DEFAULT_SIZES.to_vec();
+    let mut bit_widths = DEFAULT_BIT_WIDTHS.to_vec();
+    let mut builds = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sizes" => {
+                let v = args.next().ok_or_else(|| anyhow!("missing value for --sizes"))?;
+                sizes = v.split(',').map(|s| s.parse()).collect::<Result<_, _>>()?;
+            }
+            "--bit-widths" => {
+                let v = args.next().ok_or_else(|| anyhow!("missing value for --bit-widths"))?;
+                bit_widths = v.split(',').map(|s| s.parse()).collect::<Result<_, _>>()?;
+            }
+            "--wasm" => {
+                let v = args.next().ok_or_else(|| anyhow!("missing value for --wasm"))?;
+                let (label, path) = v.split_once('=').ok_or_else(|| anyhow!("expected --wasm <LABEL>=<PATH>"))?;
+                builds.push((label.to_string(), path.to_string()));
+            }
+            // passed by `cargo bench`
+            "--bench" => {}
+            _ => return Err(anyhow!("unknown argument {}", arg)),
+        }
+    }
+    if builds.is_empty() {
+        builds.push((String::from("default"), String::from(DEFAULT_WASM)));
+    }
+    Ok(Options { sizes, bit_widths, builds })
+}
+
+fn main() -> Result<()> {
+    let options = options()?;
+    for (label, path) in &options.builds {
+        let wasm = std::fs::read(path).map_err(|e| anyhow!("cannot read {}: {}", path, e))?;
+
+        let mut headers = vec![];
+        let mut columns = vec![];
+        for bit_width in &options.bit_widths {
+            for size in &options.sizes {
+                let (mut registry, init_gas) = Registry::new(&wasm, *size, *bit_width)?;
+                let mut gas = registry.run()?;
+                gas.insert(Method::Init, init_gas);
+                headers.push(format!("w={} n={}", bit_width, size));
+                columns.push(gas);
+            }
+        }
+
+        println!("\n{} ({})", label, path);
+        print!("{:>3} {:<24}", "#", "method");
+        for header in &headers {
+            print!(" {:>14}", header);
+        }
+        println!();
+        for method in uptime_checker::client::METHODS {
+            print!("{:>3} {:<24}", method.num(), method.name());
+            for gas in &columns {
+                match gas.get(&method) {
+                    Some(g) => print!(" {:>14}", g),
+                    None => print!(" {:>14}", "-"),
+                }
+            }
+            println!();
+        }
+    }
+    Ok(())
+}
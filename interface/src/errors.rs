@@ -0,0 +1,77 @@
+//! The errors the actor aborts with. They are mapped onto the standard FVM
+//! user exit codes, consistently with the builtin actors, so several errors
+//! share an exit code. The abort message tells them apart: it starts with
+//! the name of the error, followed by its details, e.g.
+//! `NotExists: not found`.
+
+/// Same values as `fvm_shared::error::ExitCode::USR_*`
+pub const USR_ILLEGAL_ARGUMENT: u32 = 16;
+pub const USR_NOT_FOUND: u32 = 17;
+pub const USR_FORBIDDEN: u32 = 18;
+pub const USR_INSUFFICIENT_FUNDS: u32 = 19;
+pub const USR_ILLEGAL_STATE: u32 = 20;
+pub const USR_SERIALIZATION: u32 = 21;
+pub const USR_UNSPECIFIED: u32 = 23;
+
+/// An entry of the error catalogue
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ErrorCode {
+    /// Stable name of the error, the variant of the actor's `Error`
+    pub name: &'static str,
+    pub exit_code: u32,
+    pub description: &'static str,
+}
+
+const fn entry(name: &'static str, exit_code: u32, description: &'static str) -> ErrorCode {
+    ErrorCode { name, exit_code, description }
+}
+
+/// Every error the actor can abort with, one entry per variant of the actor's
+/// `Error`. Failed syscalls (`FVMSharedErrorNum`) give their error number in
+/// the abort message.
+pub const CATALOGUE: &[ErrorCode] = &[
+    entry("AlreadyVoted", USR_FORBIDDEN, "the voter already voted in the current round"),
+    entry("CannotDeserialize", USR_SERIALIZATION, "the params or the state cannot be decoded"),
+    entry("FVMIpldHamt", USR_ILLEGAL_STATE, "a state HAMT operation failed"),
+    entry("Anyhow", USR_ILLEGAL_STATE, "an internal operation failed"),
+    entry("FVMSharedErrorNum", USR_UNSPECIFIED, "a syscall failed, with the given error number"),
+    entry("FVMSDKNoState", USR_ILLEGAL_STATE, "the actor has no state"),
+    entry("FVMEncoding", USR_SERIALIZATION, "a value cannot be CBOR encoded or decoded"),
+    entry("FVMSharedAddress", USR_ILLEGAL_ARGUMENT, "an address is malformed"),
+    entry("UnresolvedAddress", USR_NOT_FOUND, "an address does not resolve to an actor"),
+    entry("NotOwner", USR_FORBIDDEN, "the caller does not own the node"),
+    entry("NotExists", USR_NOT_FOUND, "the node, namespace or round does not exist"),
+    entry("NotCaller", USR_FORBIDDEN, "the caller is not allowed to call the method"),
+    entry("InvalidQuorum", USR_ILLEGAL_ARGUMENT, "the quorum is not a fraction below one"),
+    entry("InvalidPeerID", USR_ILLEGAL_ARGUMENT, "the PeerID is malformed"),
+    entry("InvalidMultiAddr", USR_ILLEGAL_ARGUMENT, "the multiaddr is malformed"),
+    entry("TooManyAddresses", USR_ILLEGAL_ARGUMENT, "the node has too many multiaddrs"),
+    entry("InvalidHealthCheck", USR_ILLEGAL_ARGUMENT, "a health check is malformed"),
+    entry("NotAssigned", USR_FORBIDDEN, "the checker is not assigned to the member"),
+    entry("FutureEpoch", USR_ILLEGAL_ARGUMENT, "the epoch is in the future"),
+    entry("InvalidParams", USR_ILLEGAL_ARGUMENT, "the params are inconsistent"),
+    entry("WrongVotingPhase", USR_ILLEGAL_STATE, "the vote is not accepted in the current phase or mode"),
+    entry("InvalidCommitment", USR_ILLEGAL_ARGUMENT, "the reveal does not match the commitment"),
+    entry("EvictionPending", USR_ILLEGAL_STATE, "the checker eviction is pending"),
+    entry("Jailed", USR_ILLEGAL_STATE, "the checker is jailed"),
+    entry("AlreadyExists", USR_ILLEGAL_ARGUMENT, "the node or namespace already exists"),
+    entry("InsufficientDeposit", USR_INSUFFICIENT_FUNDS, "the message value is below the member deposit"),
+    entry("MemberCapReached", USR_ILLEGAL_STATE, "the maximum number of members is reached"),
+    entry("CreatorCapReached", USR_ILLEGAL_STATE, "the creator reached its maximum number of members"),
+    entry("RefundFailed", USR_ILLEGAL_STATE, "the deposit refund failed"),
+    entry("TooManySubscribers", USR_ILLEGAL_STATE, "the maximum number of subscribers is reached"),
+    entry("StateVersion", USR_ILLEGAL_STATE, "the state layout is not supported, see migrate"),
+    entry("BatchFailed", USR_ILLEGAL_ARGUMENT, "items of a batch failed, each listed with its index and error"),
+];
+
+/// Looks up the catalogue entry of an error by name.
+pub fn lookup(name: &str) -> Option<&'static ErrorCode> {
+    CATALOGUE.iter().find(|e| e.name == name)
+}
+
+/// Splits an abort message into the catalogue entry of its error and
+/// its details.
+pub fn parse_abort_message(msg: &str) -> Option<(&'static ErrorCode, &str)> {
+    let (name, details) = msg.split_once(": ").unwrap_or((msg, ""));
+    lookup(name).map(|e| (e, details))
+}
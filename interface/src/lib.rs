@@ -0,0 +1,73 @@
+//! Types to query the uptime checker actor from other actors. The params
+//! and return values of the methods below are CBOR encoded as tuples, so
+//! they stay stable as long as fields are only ever appended.
+use serde_tuple::{Deserialize_tuple, Serialize_tuple};
+
+pub mod errors;
+
+/// The libp2p peer id representation
+pub type PeerID = String;
+/// Identifies one of the independent registries of the actor
+pub type NamespaceId = String;
+/// Epoch number of the chain, same as `fvm_shared::clock::ChainEpoch`
+pub type ChainEpoch = i64;
+
+/// Params: `MemberQuery`, returns: `MemberStatus`
+pub const METHOD_MEMBER_STATUS: u64 = 22;
+/// Params: `UptimeQuery`, returns: `Uptime`
+pub const METHOD_MEMBER_UPTIME: u64 = 23;
+/// Params: `CheckerQuery`, returns: `CheckerStatus`
+pub const METHOD_CHECKER_STATUS: u64 = 24;
+
+#[derive(Debug, Clone, Serialize_tuple, Deserialize_tuple, Eq, PartialEq)]
+pub struct MemberQuery {
+    /// The namespace the node is registered in
+    pub namespace: NamespaceId,
+    pub member: PeerID,
+}
+
+#[derive(Debug, Clone, Serialize_tuple, Deserialize_tuple, Eq, PartialEq)]
+pub struct MemberStatus {
+    /// Whether the member is registered at all
+    pub registered: bool,
+    /// Whether the member is currently considered online: it was not
+    /// declared offline since it was registered or last edited by its
+    /// owner, an edit clears the verdict
+    pub online: bool,
+    /// Epoch since which the member is declared offline, if it is
+    pub offline_since: Option<ChainEpoch>,
+}
+
+#[derive(Debug, Clone, Serialize_tuple, Deserialize_tuple, Eq, PartialEq)]
+pub struct UptimeQuery {
+    /// The namespace the node is registered in
+    pub namespace: NamespaceId,
+    pub member: PeerID,
+    /// Length of the window, ending at the current epoch
+    pub epochs: ChainEpoch,
+}
+
+#[derive(Debug, Clone, Serialize_tuple, Deserialize_tuple, Eq, PartialEq)]
+pub struct Uptime {
+    /// Epochs of the window the member was registered in
+    pub tracked_epochs: ChainEpoch,
+    /// Epochs of the window the member was not declared offline
+    pub online_epochs: ChainEpoch,
+}
+
+#[derive(Debug, Clone, Serialize_tuple, Deserialize_tuple, Eq, PartialEq)]
+pub struct CheckerQuery {
+    /// The namespace the node is registered in
+    pub namespace: NamespaceId,
+    pub checker: PeerID,
+}
+
+#[derive(Debug, Clone, Serialize_tuple, Deserialize_tuple, Eq, PartialEq)]
+pub struct CheckerStatus {
+    /// Whether the checker is registered at all
+    pub registered: bool,
+    /// Whether the checker is active, i.e. not jailed
+    pub active: bool,
+    /// The number of times the checker was jailed
+    pub strikes: u32,
+}
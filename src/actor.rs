@@ -1,10 +1,31 @@
 use std::marker::PhantomData;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::{ActorID, METHOD_SEND};
+use num_traits::Zero;
+use crate::assignment::{assigned_checkers, window_randomness, Rank};
+use crate::runtime;
 use crate::traits::{LoadableState, UptimeCheckerActor};
-use crate::types::{InitParams, MultiAddr, NodeInfo, NodeInfoPayload, PeerID, ReportPayload};
+use crate::types::{
+    validate_batch_size, validate_namespace, AcceptOwnershipPayload, AssignmentsPage, AssignmentsQuery,
+    CheckersReportBatchPayload, CommitReportPayload, CommitRound, InitParams, MembersBatchPayload, MultiAddr, NamespaceId, NodeIdPayload, NodeInfo, NodeInfoPayload, NodeKind, PeerID,
+    PendingEviction, ReportMemberPayload, ReportPayload, RevealReportPayload, StatusChange, StatusNotification,
+    SubscribePayload, SubscriberPayload, Subscription, TransferOwnershipPayload, VotePhase, VotingMode,
+    MAX_ASSIGNMENT_SCORES,
+};
+use crate::util::resolve_address;
 use crate::{ensure, Error};
+use uptime_checker_interface::{
+    CheckerQuery, CheckerStatus, MemberQuery, MemberStatus, Uptime, UptimeQuery,
+};
 
-const THRESHOLD_NUMERATOR: usize = 20000;
-const THRESHOLD_DENOMINATOR: usize = 30000;
+/// The only actors allowed to call the constructor
+const SYSTEM_ACTOR_ID: ActorID = 0;
+const INIT_ACTOR_ID: ActorID = 1;
+/// The gas each subscriber may use to handle the notifications of a call
+const NOTIFICATION_GAS_LIMIT: i64 = 10_000_000;
 
 pub struct Actor<S: LoadableState> {
     _phantom: PhantomData<S>
@@ -12,90 +33,994 @@ pub struct Actor<S: LoadableState> {
 
 impl <S: LoadableState> UptimeCheckerActor for Actor<S> {
     fn init(params: InitParams) -> Result<(), Error> {
-        // TODO: perform simple checks
-        let len = params.ids.len();
-        let mut nodes = vec![];
-        for i in 0..len {
-            nodes.push(NodeInfo::new(
-                PeerID::from(&params.ids[i]),
-                params.creators[i],
-                params.addresses[i]
-                    .iter()
-                    .map(MultiAddr::from)
-                    .collect(),
-            ));
-        }
-        let state = S::new(nodes, &params.voting_duration)?;
-        state.save()?;
-        Ok(())
+        let caller = runtime::caller();
+        ensure!(caller == SYSTEM_ACTOR_ID || caller == INIT_ACTOR_ID, Error::NotCaller)?;
+
+        let creators = params.creator_ids()?;
+        S::init_root(creators.clone())?;
+        Self::create_namespace(params, creators)
     }
 
     fn new_checker(c: NodeInfoPayload) -> Result<(), Error> {
-        let mut s = S::load()?;
-        s.upsert_checker(NodeInfo::from(c))?;
+        let mut s = S::load(&c.namespace)?;
+        let node = NodeInfo::try_from(c)?;
+        ensure!(s.node(NodeKind::Checker, node.id())?.is_none(), Error::AlreadyExists)?;
+        s.upsert_checker(node)?;
         s.save()?;
         Ok(())
     }
 
     fn new_member(m: NodeInfoPayload) -> Result<(), Error> {
-        let mut s = S::load()?;
-        s.upsert_node(NodeInfo::from(m))?;
+        let mut s = S::load(&m.namespace)?;
+        Self::register_member(&mut s, m, runtime::value_received())?;
         s.save()?;
         Ok(())
     }
 
     fn edit_checker(c: NodeInfoPayload) -> Result<(), Error> {
-        let mut s = S::load()?;
-        s.upsert_checker(NodeInfo::from(c))?;
+        let mut s = S::load(&c.namespace)?;
+        let node = NodeInfo::try_from(c)?;
+        // only the new_* methods insert, past their checks
+        ensure!(s.node(NodeKind::Checker, node.id())?.is_some(), Error::NotExists)?;
+        s.upsert_checker(node)?;
         s.save()?;
         Ok(())
     }
 
     fn edit_member(m: NodeInfoPayload) -> Result<(), Error> {
-        let mut s = S::load()?;
-        s.upsert_node(NodeInfo::from(m))?;
+        let mut s = S::load(&m.namespace)?;
+        let node = NodeInfo::try_from(m)?;
+        // an insert here would skip the member deposit
+        ensure!(s.is_member(node.id())?, Error::NotExists)?;
+        s.upsert_node(node)?;
         s.save()?;
         Ok(())
     }
 
-    fn rm_checker() -> Result<(), Error> {
-        let mut s = S::load()?;
-        s.remove_checker(&fvm_sdk::message::caller())?;
+    fn rm_checker(p: NodeIdPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        s.remove_checker(&p.id)?;
         s.save()?;
         Ok(())
     }
 
-    fn rm_member() -> Result<(), Error> {
-        let mut s = S::load()?;
-        s.remove_node(&fvm_sdk::message::caller())?;
+    fn rm_member(p: NodeIdPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        let node = s.remove_node(&p.id)?;
         s.save()?;
+
+        if !node.deposit().is_zero() {
+            let receipt = runtime::send(
+                &Address::new_id(*node.creator()),
+                METHOD_SEND,
+                RawBytes::default(),
+                node.deposit().clone(),
+            )?;
+            ensure!(receipt.exit_code.is_success(), Error::RefundFailed)?;
+        }
+
         Ok(())
     }
 
     fn report_checker(p: ReportPayload) -> Result<(), Error> {
-        let mut s = S::load()?;
-        let caller = fvm_sdk::message::caller();
+        let mut s = S::load(&p.namespace)?;
+        let mut outbox = vec![];
+        Self::ensure_instant_voter(&s, &p.voter)?;
+        if Self::vote_offline(&mut s, &p.checker, &p.voter)? {
+            outbox.push(Self::notification(&mut s, &p.checker, StatusChange::CheckerJailed));
+        }
+        s.save()?;
+        Self::notify(&p.namespace, &s, outbox)
+    }
+
+    fn transfer_ownership(p: TransferOwnershipPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        let new_owner = resolve_address(&p.new_owner)?;
+        s.transfer_ownership(p.kind, &p.id, new_owner, p.require_accept)?;
+        s.save()?;
+        Ok(())
+    }
+
+    fn accept_ownership(p: AcceptOwnershipPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        s.accept_ownership(p.kind, &p.id)?;
+        s.save()?;
+        Ok(())
+    }
+
+    fn report_member(p: ReportMemberPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        let mut outbox = vec![];
+        let caller = runtime::caller();
+        let epoch = runtime::curr_epoch();
+
+        ensure!(s.is_checker(&p.voter, &caller)?, Error::NotCaller)?;
+        ensure!(s.is_member(&p.member)?, Error::NotExists)?;
+
+        let assigned = Self::assigned_to(&s, &p.member, epoch)?;
+        ensure!(assigned.contains(&p.voter), Error::NotAssigned)?;
+        ensure!(
+            !s.has_voted(NodeKind::Member, &p.member, &p.voter)?,
+            Error::AlreadyVoted(p.voter.clone())
+        )?;
+
+        let votes = s.record_voted(NodeKind::Member, &p.member, &p.voter)?;
+        let offline = s.quorum().is_reached(votes, assigned.len());
+        if offline {
+            s.set_member_offline(&p.member, epoch)?;
+            outbox.push(Self::notification(&mut s, &p.member, StatusChange::MemberOffline));
+        }
+
+        s.save()?;
+        Self::notify(&p.namespace, &s, outbox)
+    }
+
+    fn get_assignments(p: AssignmentsQuery) -> Result<AssignmentsPage, Error> {
+        ensure!(p.epoch <= runtime::curr_epoch(), Error::FutureEpoch)?;
 
-        ensure!(s.is_checker(&caller)?, Error::NotCaller)?;
-        ensure!(!s.has_voted(&p.checker, &caller)?, Error::AlreadyVoted(caller))?;
+        let s = S::load(&p.namespace)?;
+        let randomness = window_randomness(p.epoch, s.assignment_window())?;
+        let k = s.checkers_per_member();
 
-        let votes = s.record_voted(&p.checker, &caller)?;
+        // every member scanned scores up to every checker
+        let cap = (MAX_ASSIGNMENT_SCORES / s.total_checkers().max(1)).max(1) as u64;
+        let limit = p.limit.unwrap_or(cap).min(cap);
+        ensure!(limit > 0, Error::InvalidParams(String::from("limit must be positive")))?;
 
-        // perform checks
-        let total_checkers = s.total_checkers();
-        if Self::calculate_voting_threshold(total_checkers) < votes {
-            s.remove_checker_unchecked(&p.checker)?;
+        // only the page is collected, the walk stops on the first member
+        // of the next one
+        let end = p.offset.saturating_add(limit);
+        let (mut index, mut next, mut page) = (0, None, vec![]);
+        s.for_each_id(NodeKind::Member, |m| {
+            if index == end {
+                next = Some(end);
+                return false;
+            }
+            if index >= p.offset {
+                page.push(m.clone());
+            }
+            index += 1;
+            true
+        })?;
+
+        let mut members = vec![];
+        for m in page {
+            let mut rank = Rank::new(&randomness, &m, &p.checker, k);
+            s.for_each_id(NodeKind::Checker, |c| rank.add(c))?;
+            if rank.is_assigned() {
+                members.push(m);
+            }
+        }
+        Ok(AssignmentsPage { members, next })
+    }
+
+    fn commit_report(p: CommitReportPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        let mut outbox = vec![];
+        let caller = runtime::caller();
+        let epoch = runtime::curr_epoch();
+        let (commit, reveal) = Self::commit_reveal_durations(&s)?;
+
+        ensure!(s.is_checker(&p.voter, &caller)?, Error::NotCaller)?;
+        Self::ensure_active_checker(&s, &p.checker)?;
+        ensure!(s.pending_eviction(&p.checker)?.is_none(), Error::EvictionPending)?;
+
+        let mut jailed = false;
+        let mut round = match s.commit_round(&p.checker)? {
+            Some(r) if r.phase(epoch, commit, reveal) != VotePhase::Over => r,
+            Some(r) => {
+                jailed = Self::finalize_round(&mut s, &p.checker, &r)?;
+                CommitRound::new(epoch)
+            }
+            None => CommitRound::new(epoch),
+        };
+        ensure!(round.phase(epoch, commit, reveal) == VotePhase::Commit, Error::WrongVotingPhase)?;
+
+        round.commit(&p.voter, p.commitment)?;
+        s.set_commit_round(&p.checker, Some(round))?;
+        if jailed {
+            outbox.push(Self::notification(&mut s, &p.checker, StatusChange::CheckerJailed));
+        }
+
+        s.save()?;
+        Self::notify(&p.namespace, &s, outbox)
+    }
+
+    fn reveal_report(p: RevealReportPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        let mut outbox = vec![];
+        let caller = runtime::caller();
+        let epoch = runtime::curr_epoch();
+        let (commit, reveal) = Self::commit_reveal_durations(&s)?;
+
+        ensure!(s.is_checker(&p.voter, &caller)?, Error::NotCaller)?;
+
+        let mut round = s.commit_round(&p.checker)?.ok_or(Error::NotExists)?;
+        ensure!(round.phase(epoch, commit, reveal) == VotePhase::Reveal, Error::WrongVotingPhase)?;
+
+        round.reveal(&p.checker, &p.voter, p.offline, &p.salt)?;
+        if !round.all_revealed() {
+            s.set_commit_round(&p.checker, Some(round))?;
+        } else if Self::finalize_round(&mut s, &p.checker, &round)? {
+            outbox.push(Self::notification(&mut s, &p.checker, StatusChange::CheckerJailed));
+        }
+
+        s.save()?;
+        Self::notify(&p.namespace, &s, outbox)
+    }
+
+    fn finalize_report(p: NodeIdPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        let mut outbox = vec![];
+        let epoch = runtime::curr_epoch();
+        let (commit, reveal) = Self::commit_reveal_durations(&s)?;
+
+        let round = s.commit_round(&p.id)?.ok_or(Error::NotExists)?;
+        ensure!(round.phase(epoch, commit, reveal) == VotePhase::Over, Error::WrongVotingPhase)?;
+
+        if Self::finalize_round(&mut s, &p.id, &round)? {
+            outbox.push(Self::notification(&mut s, &p.id, StatusChange::CheckerJailed));
         }
 
+        s.save()?;
+        Self::notify(&p.namespace, &s, outbox)
+    }
+
+    fn appeal_eviction(p: NodeIdPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        let caller = runtime::caller();
+
+        ensure!(s.is_checker(&p.id, &caller)?, Error::NotOwner)?;
+
+        let mut pending = Self::open_eviction(&s, &p.id)?;
+        pending.appealed = true;
+        s.set_pending_eviction(&p.id, Some(pending))?;
+
         s.save()?;
 
         Ok(())
     }
+
+    fn vouch_online(p: ReportPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        let caller = runtime::caller();
+
+        ensure!(s.is_checker(&p.voter, &caller)?, Error::NotCaller)?;
+        ensure!(p.voter != p.checker, Error::NotCaller)?;
+
+        let mut pending = Self::open_eviction(&s, &p.checker)?;
+        pending.vouch(&p.voter)?;
+        s.set_pending_eviction(&p.checker, Some(pending))?;
+
+        s.save()?;
+
+        Ok(())
+    }
+
+    fn finalize_eviction(p: NodeIdPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        let mut outbox = vec![];
+        let epoch = runtime::curr_epoch();
+
+        let pending = s.pending_eviction(&p.id)?.ok_or(Error::NotExists)?;
+        ensure!(!pending.is_open(epoch, s.appeal_window()), Error::EvictionPending)?;
+
+        if !pending.appeal_succeeds(s.quorum(), s.total_checkers()) && s.jail_checker(&p.id)? {
+            outbox.push(Self::notification(&mut s, &p.id, StatusChange::CheckerJailed));
+        }
+        s.set_pending_eviction(&p.id, None)?;
+
+        s.save()?;
+        Self::notify(&p.namespace, &s, outbox)
+    }
+
+    fn unjail(p: NodeIdPayload) -> Result<(), Error> {
+        let mut s = S::load(&p.namespace)?;
+        s.unjail_checker(&p.id)?;
+        s.save()?;
+        Ok(())
+    }
+
+    fn subscribe(p: SubscribePayload) -> Result<(), Error> {
+        ensure!(S::is_admin(&runtime::caller())?, Error::NotCaller)?;
+        let mut s = S::load(&p.namespace)?;
+        s.subscribe(resolve_address(&p.subscriber)?, p.method)?;
+        s.save()?;
+        Ok(())
+    }
+
+    fn unsubscribe(p: SubscriberPayload) -> Result<(), Error> {
+        let caller = runtime::caller();
+        let subscriber = resolve_address(&p.subscriber)?;
+        ensure!(caller == subscriber || S::is_admin(&caller)?, Error::NotCaller)?;
+
+        let mut s = S::load(&p.namespace)?;
+        s.unsubscribe(&subscriber)?;
+        s.save()?;
+        Ok(())
+    }
+
+    fn member_status(p: MemberQuery) -> Result<MemberStatus, Error> {
+        let s = S::load(&p.namespace)?;
+        Ok(match s.node(NodeKind::Member, &p.member)? {
+            Some(n) => MemberStatus {
+                registered: true,
+                online: n.offline_since().is_none(),
+                offline_since: *n.offline_since(),
+            },
+            None => MemberStatus { registered: false, online: false, offline_since: None },
+        })
+    }
+
+    fn member_uptime(p: UptimeQuery) -> Result<Uptime, Error> {
+        ensure!(p.epochs >= 0, Error::InvalidParams(String::from("epochs must not be negative")))?;
+
+        let s = S::load(&p.namespace)?;
+        let n = s.node(NodeKind::Member, &p.member)?.ok_or(Error::NotExists)?;
+
+        let now = runtime::curr_epoch();
+        let (tracked_epochs, online_epochs) = n.uptime(now - p.epochs, now);
+        Ok(Uptime { tracked_epochs, online_epochs })
+    }
+
+    fn checker_status(p: CheckerQuery) -> Result<CheckerStatus, Error> {
+        let s = S::load(&p.namespace)?;
+        Ok(match s.node(NodeKind::Checker, &p.checker)? {
+            Some(n) => CheckerStatus {
+                registered: true,
+                active: n.is_active(),
+                strikes: n.strikes(),
+            },
+            None => CheckerStatus { registered: false, active: false, strikes: 0 },
+        })
+    }
+
+    fn new_namespace(params: InitParams) -> Result<(), Error> {
+        ensure!(S::is_admin(&runtime::caller())?, Error::NotCaller)?;
+        ensure!(!S::has_namespace(&params.namespace)?, Error::AlreadyExists)?;
+        let creators = params.creator_ids()?;
+        Self::create_namespace(params, creators)
+    }
+
+    fn migrate() -> Result<(), Error> {
+        S::migrate()
+    }
+
+    fn register_members_batch(p: MembersBatchPayload) -> Result<(), Error> {
+        validate_batch_size(p.members.len())?;
+        let mut s = S::load(&p.namespace)?;
+
+        let len = p.members.len();
+        let value = runtime::value_received();
+        ensure!(value >= s.member_deposit().clone() * len, Error::InsufficientDeposit)?;
+        // every member holds an equal share of the value, so that the
+        // refunds add up to it, the first one also holds the remainder
+        let share = value.clone() / len;
+        let mut remainder = value - share.clone() * len;
+
+        let mut errors = vec![];
+        for (i, m) in p.payloads().into_iter().enumerate() {
+            let deposit = share.clone() + std::mem::take(&mut remainder);
+            if let Err(e) = Self::register_member(&mut s, m, deposit) {
+                errors.push((i, e));
+            }
+        }
+        ensure!(errors.is_empty(), Error::BatchFailed(errors))?;
+
+        s.save()?;
+        Ok(())
+    }
+
+    fn report_checkers_batch(p: CheckersReportBatchPayload) -> Result<(), Error> {
+        validate_batch_size(p.checkers.len())?;
+        let mut s = S::load(&p.namespace)?;
+        let mut outbox = vec![];
+        Self::ensure_instant_voter(&s, &p.voter)?;
+
+        let mut errors = vec![];
+        for (i, checker) in p.checkers.iter().enumerate() {
+            match Self::vote_offline(&mut s, checker, &p.voter) {
+                Ok(true) => outbox.push(Self::notification(&mut s, checker, StatusChange::CheckerJailed)),
+                Ok(false) => {}
+                Err(e) => errors.push((i, e)),
+            }
+        }
+        ensure!(errors.is_empty(), Error::BatchFailed(errors))?;
+
+        s.save()?;
+        Self::notify(&p.namespace, &s, outbox)
+    }
+
+    fn deliver_notifications(p: SubscriberPayload) -> Result<(), Error> {
+        let subscriber = resolve_address(&p.subscriber)?;
+        ensure!(runtime::caller() == subscriber, Error::NotCaller)?;
+
+        let mut s = S::load(&p.namespace)?;
+        let (method, notifications) = s.take_undelivered(&subscriber)?;
+        // saved first, so that a subscriber calling back is not sent them again
+        s.save()?;
+
+        let failed = Self::send_notifications(&Subscription::new(subscriber, method), notifications);
+        Self::keep_undelivered(&p.namespace, vec![(subscriber, failed)])
+    }
 }
 
 impl <S: LoadableState> Actor<S> {
-    fn calculate_voting_threshold(total: usize) -> usize {
-        // numerator is by default 2 and denominator is 3, hence the ratio is 0.67
-        total * THRESHOLD_NUMERATOR / THRESHOLD_DENOMINATOR
+    /// Validates the params and stores the state of a new namespace,
+    /// `creators` being the resolved `params.creators`.
+    fn create_namespace(params: InitParams, creators: Vec<ActorID>) -> Result<(), Error> {
+        validate_namespace(&params.namespace)?;
+        params.validate()?;
+
+        let mut nodes = vec![];
+        let nodes_params = params.ids.iter().zip(creators).zip(&params.addresses);
+        for (i, ((id, creator), addresses)) in nodes_params.enumerate() {
+            nodes.push(NodeInfo::new(
+                PeerID::from(id),
+                creator,
+                addresses
+                    .iter()
+                    .map(MultiAddr::from)
+                    .collect(),
+                params.health_checks.get(i).cloned().unwrap_or_default(),
+            )?);
+        }
+        let state = S::new(&params.namespace, nodes, &params)?;
+        state.save()?;
+        Ok(())
+    }
+
+    /// Registers a member of the caller holding `deposit`.
+    fn register_member(s: &mut S, m: NodeInfoPayload, deposit: TokenAmount) -> Result<(), Error> {
+        let mut node = NodeInfo::try_from(m)?;
+        ensure!(!s.is_member(node.id())?, Error::AlreadyExists)?;
+        ensure!(deposit >= *s.member_deposit(), Error::InsufficientDeposit)?;
+        node.set_deposit(deposit);
+        s.upsert_node(node)
+    }
+
+    /// Checks offline votes are counted right away and the caller owns
+    /// the `voter` checker.
+    fn ensure_instant_voter(s: &S, voter: &PeerID) -> Result<(), Error> {
+        ensure!(*s.voting_mode() == VotingMode::Instant, Error::WrongVotingPhase)?;
+        ensure!(s.is_checker(voter, &runtime::caller())?, Error::NotCaller)
+    }
+
+    /// Checks that `checker` is registered and not jailed, so that votes
+    /// are only recorded against checkers that can be evicted.
+    fn ensure_active_checker(s: &S, checker: &PeerID) -> Result<(), Error> {
+        match s.node(NodeKind::Checker, checker)? {
+            Some(info) => ensure!(info.is_active(), Error::Jailed),
+            None => Err(Error::NotExists),
+        }
+    }
+
+    /// Records the offline vote of `voter` on `checker`, starting its
+    /// eviction once the quorum is reached. Returns whether the checker
+    /// was jailed.
+    fn vote_offline(s: &mut S, checker: &PeerID, voter: &PeerID) -> Result<bool, Error> {
+        Self::ensure_active_checker(s, checker)?;
+        ensure!(s.pending_eviction(checker)?.is_none(), Error::EvictionPending)?;
+        ensure!(
+            !s.has_voted(NodeKind::Checker, checker, voter)?,
+            Error::AlreadyVoted(voter.clone())
+        )?;
+
+        let votes = s.record_voted(NodeKind::Checker, checker, voter)?;
+        if s.quorum().is_reached(votes, s.total_checkers()) {
+            s.clear_votes(NodeKind::Checker, checker)?;
+            return Self::evict(s, checker);
+        }
+        Ok(false)
+    }
+
+    fn commit_reveal_durations(s: &S) -> Result<(ChainEpoch, ChainEpoch), Error> {
+        match s.voting_mode() {
+            VotingMode::CommitReveal { commit_duration, reveal_duration } => {
+                Ok((*commit_duration, *reveal_duration))
+            }
+            VotingMode::Instant => Err(Error::WrongVotingPhase),
+        }
+    }
+
+    /// Tallies the revealed votes of a round, evicting the checker if
+    /// the quorum is reached, and closes the round. Returns whether the
+    /// checker was jailed.
+    fn finalize_round(s: &mut S, reported: &PeerID, round: &CommitRound) -> Result<bool, Error> {
+        let (offline, revealed) = round.tally();
+        let mut jailed = false;
+        if s.quorum().is_reached(revealed, s.total_checkers())
+            && s.quorum().is_reached(offline, revealed)
+        {
+            jailed = Self::evict(s, reported)?;
+        }
+        s.set_commit_round(reported, None)?;
+        Ok(jailed)
+    }
+
+    /// Starts the eviction of a checker voted offline. Without an
+    /// appeal window the checker is jailed right away, in which case
+    /// it returns true.
+    fn evict(s: &mut S, checker: &PeerID) -> Result<bool, Error> {
+        if s.appeal_window() == 0 {
+            return s.jail_checker(checker);
+        }
+        let pending = PendingEviction::new(runtime::curr_epoch());
+        s.set_pending_eviction(checker, Some(pending))?;
+        Ok(false)
+    }
+
+    /// A status change of a node, to send the subscribers with `notify`.
+    fn notification(s: &mut S, id: &PeerID, change: StatusChange) -> StatusNotification {
+        s.new_notification(id, change, runtime::curr_epoch())
+    }
+
+    /// Sends the status changes to every subscriber of the namespace, once
+    /// the state `s` recording them is saved. A subscriber failing or
+    /// running out of its gas limit cannot revert the status changes, the
+    /// notifications it missed are kept for `deliver_notifications`.
+    fn notify(namespace: &NamespaceId, s: &S, outbox: Vec<StatusNotification>) -> Result<(), Error> {
+        if outbox.is_empty() {
+            return Ok(());
+        }
+        let failed = s
+            .subscriptions()?
+            .iter()
+            .map(|sub| (sub.actor, Self::send_notifications(sub, outbox.clone())))
+            .collect();
+        Self::keep_undelivered(namespace, failed)
+    }
+
+    /// Sends the notifications to the subscriber in order, until it used
+    /// up `NOTIFICATION_GAS_LIMIT`. Returns those it did not handle.
+    ///
+    /// The SDK cannot cap the gas of a single send, so the limit is
+    /// checked between sends from the gas the receipts report.
+    fn send_notifications(sub: &Subscription, notifications: Vec<StatusNotification>) -> Vec<StatusNotification> {
+        let to = Address::new_id(sub.actor);
+        let mut gas_used = 0;
+        let mut failed = vec![];
+        for n in notifications {
+            if gas_used >= NOTIFICATION_GAS_LIMIT {
+                failed.push(n);
+                continue;
+            }
+            let sent = RawBytes::serialize(&n)
+                .map_err(Error::from)
+                .and_then(|params| runtime::send(&to, sub.method, params, TokenAmount::default()));
+            match sent {
+                Ok(r) => {
+                    gas_used += r.gas_used;
+                    if !r.exit_code.is_success() {
+                        failed.push(n);
+                    }
+                }
+                // e.g. the subscriber was deleted
+                Err(_) => failed.push(n),
+            }
+        }
+        failed
+    }
+
+    /// Records the notifications the subscribers did not receive. The state
+    /// is loaded again, as the subscribers may have changed it meanwhile.
+    fn keep_undelivered(namespace: &NamespaceId, failed: Vec<(ActorID, Vec<StatusNotification>)>) -> Result<(), Error> {
+        if failed.iter().all(|(_, f)| f.is_empty()) {
+            return Ok(());
+        }
+        let mut s = S::load(namespace)?;
+        for (subscriber, notifications) in failed {
+            if !notifications.is_empty() {
+                s.add_undelivered(&subscriber, notifications)?;
+            }
+        }
+        s.save()?;
+        Ok(())
+    }
+
+    /// The pending eviction of `checker`, if its appeal window is still open.
+    fn open_eviction(s: &S, checker: &PeerID) -> Result<PendingEviction, Error> {
+        let pending = s.pending_eviction(checker)?.ok_or(Error::NotExists)?;
+        ensure!(
+            pending.is_open(runtime::curr_epoch(), s.appeal_window()),
+            Error::WrongVotingPhase
+        )?;
+        Ok(pending)
+    }
+
+    /// The checkers assigned to `member` in the window of `epoch`.
+    fn assigned_to(s: &S, member: &PeerID, epoch: ChainEpoch) -> Result<Vec<PeerID>, Error> {
+        let randomness = window_randomness(epoch, s.assignment_window())?;
+        let checkers = s.checker_ids()?;
+        Ok(assigned_checkers(&randomness, member, &checkers, s.checkers_per_member()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hamt_state::HamtState;
+    use crate::runtime::mock;
+    use crate::types::{report_commitment, NamespaceId, NodeEntry, Quorum, DEFAULT_NAMESPACE};
+    use fvm_shared::MethodNum;
+    use serde_bytes::ByteBuf;
+    use cid::multihash::Multihash;
+    use cid::Cid;
+
+    type A = Actor<HamtState>;
+
+    const ADDRESS: &str = "/ip4/10.0.0.1/tcp/4001";
+
+    /// A CIDv1 PeerID inlining a fake ed25519 key, unique per `i`
+    fn peer(i: u64) -> PeerID {
+        let mut key = vec![0x08, 0x01, 0x12, 0x20];
+        key.extend_from_slice(&[0u8; 24]);
+        key.extend_from_slice(&i.to_be_bytes());
+        Cid::new_v1(0x72, Multihash::wrap(0x00, &key).unwrap()).to_string()
+    }
+
+    /// The owner of the `i`-th checker
+    fn owner(i: u64) -> ActorID {
+        100 + i
+    }
+
+    fn ns() -> NamespaceId {
+        NamespaceId::from(DEFAULT_NAMESPACE)
+    }
+
+    /// Constructs the actor in a fresh runtime, with `n` checkers, the
+    /// `i`-th being `peer(i)` of `owner(i)`.
+    fn setup(n: u64, params: InitParams) {
+        mock::reset();
+        mock::set_caller(SYSTEM_ACTOR_ID);
+        A::init(InitParams {
+            ids: (0..n).map(peer).collect(),
+            creators: (0..n).map(|i| Address::new_id(owner(i))).collect(),
+            addresses: (0..n).map(|_| vec![ADDRESS.to_string()]).collect(),
+            namespace: ns(),
+            checkers_per_member: params.checkers_per_member.or(Some(1)),
+            ..params
+        })
+        .unwrap();
+    }
+
+    fn node(id: PeerID) -> NodeInfoPayload {
+        NodeInfoPayload::new(ns(), id, vec![ADDRESS.to_string()], vec![])
+    }
+
+    fn state() -> HamtState {
+        HamtState::load(&ns()).unwrap()
+    }
+
+    fn is_jailed(id: &PeerID) -> bool {
+        !state().node(NodeKind::Checker, id).unwrap().unwrap().is_active()
+    }
+
+    /// Commits the verdict of the `voter`-th checker on `checker`, as its owner.
+    fn commit(checker: PeerID, voter: u64, offline: bool, salt: &[u8]) -> Result<(), Error> {
+        mock::set_caller(owner(voter));
+        let commitment = ByteBuf::from(report_commitment(&checker, &peer(voter), offline, salt).to_vec());
+        A::commit_report(CommitReportPayload { namespace: ns(), checker, voter: peer(voter), commitment })
+    }
+
+    fn reveal(checker: PeerID, voter: u64, offline: bool, salt: &[u8]) -> Result<(), Error> {
+        mock::set_caller(owner(voter));
+        A::reveal_report(RevealReportPayload { namespace: ns(), checker, voter: peer(voter), offline, salt: ByteBuf::from(salt.to_vec()) })
+    }
+
+    fn commit_reveal() -> InitParams {
+        InitParams {
+            voting_mode: VotingMode::CommitReveal { commit_duration: 10, reveal_duration: 10 },
+            appeal_window: Some(0),
+            ..Default::default()
+        }
+    }
+
+    /// Reports `checker` offline with the `voter`-th checker, as its owner.
+    fn report(checker: PeerID, voter: u64) -> Result<(), Error> {
+        mock::set_caller(owner(voter));
+        A::report_checker(ReportPayload { namespace: ns(), checker, voter: peer(voter) })
+    }
+
+    #[test]
+    fn assignment_pages_cover_every_member_once_per_assigned_checker() {
+        let (checkers, members, k) = (5, 23, 2);
+        setup(checkers, InitParams { checkers_per_member: Some(k), ..Default::default() });
+        mock::set_caller(owner(0));
+        for i in 0..members {
+            A::new_member(node(peer(1000 + i))).unwrap();
+        }
+        mock::set_epoch(250);
+
+        let randomness = window_randomness(250, state().assignment_window()).unwrap();
+        let ids = (0..checkers).map(peer).collect::<Vec<_>>();
+        let mut assignments = 0;
+        for checker in &ids {
+            let (mut pages, mut offset, mut assigned) = (0, Some(0), vec![]);
+            while let Some(o) = offset {
+                let query = AssignmentsQuery { namespace: ns(), checker: checker.clone(), epoch: 250, offset: o, limit: Some(4) };
+                let page = A::get_assignments(query).unwrap();
+                assigned.extend(page.members);
+                offset = page.next;
+                pages += 1;
+            }
+            assert_eq!(pages, members.div_ceil(4));
+            for m in &assigned {
+                assert!(assigned_checkers(&randomness, m, &ids, k as usize).contains(checker));
+            }
+            assignments += assigned.len();
+        }
+        assert_eq!(assignments as u64, members * k);
+
+        // pages past the end are empty, unregistered checkers have none
+        let query = |checker: PeerID, offset| AssignmentsQuery { namespace: ns(), checker, epoch: 250, offset, limit: None };
+        assert_eq!(A::get_assignments(query(peer(0), members)).unwrap(), AssignmentsPage { members: vec![], next: None });
+        assert!(A::get_assignments(query(peer(99), 0)).unwrap().members.is_empty());
+        assert!(matches!(A::get_assignments(AssignmentsQuery { epoch: 251, ..query(peer(0), 0) }), Err(Error::FutureEpoch)));
+    }
+
+    #[test]
+    fn votes_on_unknown_checkers_are_rejected() {
+        setup(4, InitParams::default());
+        assert!(matches!(report(peer(99), 0), Err(Error::NotExists)));
+        assert!(!state().has_voted(NodeKind::Checker, &peer(99), &peer(0)).unwrap());
+
+        let batch = CheckersReportBatchPayload { namespace: ns(), checkers: vec![peer(1), peer(99), peer(2)], voter: peer(0) };
+        match A::report_checkers_batch(batch) {
+            Err(Error::BatchFailed(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert!(matches!(errors[0], (1, Error::NotExists)));
+            }
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(!state().has_voted(NodeKind::Checker, &peer(1), &peer(0)).unwrap());
+    }
+
+    #[test]
+    fn checker_batches_report_every_rejected_vote() {
+        setup(4, InitParams::default());
+        report(peer(1), 0).unwrap();
+
+        let batch = CheckersReportBatchPayload {
+            namespace: ns(),
+            checkers: vec![peer(1), peer(99), peer(2), peer(98)],
+            voter: peer(0),
+        };
+        match A::report_checkers_batch(batch) {
+            Err(Error::BatchFailed(errors)) => {
+                assert_eq!(errors.len(), 3);
+                assert!(matches!(&errors[0], (0, Error::AlreadyVoted(voter)) if *voter == peer(0)));
+                assert!(matches!(errors[1], (1, Error::NotExists)));
+                assert!(matches!(errors[2], (3, Error::NotExists)));
+            }
+            r => panic!("unexpected {:?}", r),
+        }
+        // the valid vote of the batch was rolled back with the others
+        assert!(!state().has_voted(NodeKind::Checker, &peer(2), &peer(0)).unwrap());
+        assert!(state().has_voted(NodeKind::Checker, &peer(1), &peer(0)).unwrap());
+    }
+
+    #[test]
+    fn member_batches_are_all_or_nothing() {
+        setup(2, InitParams { member_deposit: Some(String::from("10")), ..Default::default() });
+        mock::set_caller(owner(0));
+        mock::with(|m| m.value = TokenAmount::from(10));
+        A::new_member(node(peer(1000))).unwrap();
+
+        let entry = |id: PeerID| NodeEntry { id, addresses: vec![ADDRESS.to_string()], health_checks: vec![] };
+        let batch = |ids: Vec<PeerID>| MembersBatchPayload { namespace: ns(), members: ids.into_iter().map(entry).collect() };
+        mock::with(|m| m.value = TokenAmount::from(39));
+        let members = vec![peer(1001), peer(1002), peer(1003), peer(1004)];
+        assert!(matches!(A::register_members_batch(batch(members)), Err(Error::InsufficientDeposit)));
+
+        mock::with(|m| m.value = TokenAmount::from(40));
+        let members = vec![peer(1001), peer(1000), peer(1002), peer(1002)];
+        match A::register_members_batch(batch(members)) {
+            Err(Error::BatchFailed(errors)) => {
+                assert_eq!(errors.len(), 2);
+                assert!(matches!(errors[0], (1, Error::AlreadyExists)));
+                assert!(matches!(errors[1], (3, Error::AlreadyExists)));
+            }
+            r => panic!("unexpected {:?}", r),
+        }
+        for id in [peer(1001), peer(1002)] {
+            assert!(!state().is_member(&id).unwrap());
+        }
+
+        // 32 split between 3 members, the first one holds the remainder
+        mock::with(|m| m.value = TokenAmount::from(32));
+        A::register_members_batch(batch(vec![peer(1001), peer(1002), peer(1003)])).unwrap();
+        let deposits: Vec<_> = (1001..=1003)
+            .map(|i| state().node(NodeKind::Member, &peer(i)).unwrap().unwrap().deposit().clone())
+            .collect();
+        assert_eq!(deposits, vec![TokenAmount::from(12), TokenAmount::from(10), TokenAmount::from(10)]);
+    }
+
+    #[test]
+    fn commit_reveal_rounds_jail_once_finalized() {
+        setup(7, commit_reveal());
+        assert!(matches!(commit(peer(99), 1, true, b"salt"), Err(Error::NotExists)));
+        for voter in 1..=6 {
+            commit(peer(0), voter, true, format!("salt{}", voter).as_bytes()).unwrap();
+        }
+        assert!(matches!(reveal(peer(0), 1, true, b"salt1"), Err(Error::WrongVotingPhase)));
+
+        mock::set_epoch(10);
+        assert!(matches!(commit(peer(0), 6, true, b"late"), Err(Error::WrongVotingPhase)));
+        assert!(matches!(reveal(peer(0), 1, true, b"salt2"), Err(Error::InvalidCommitment)));
+        assert!(matches!(reveal(peer(0), 1, false, b"salt1"), Err(Error::InvalidCommitment)));
+        for voter in 1..=5 {
+            reveal(peer(0), voter, true, format!("salt{}", voter).as_bytes()).unwrap();
+        }
+        let finalize = || A::finalize_report(NodeIdPayload { namespace: ns(), id: peer(0) });
+        assert!(matches!(finalize(), Err(Error::WrongVotingPhase)));
+        assert!(!is_jailed(&peer(0)));
+
+        // the last voter missed the reveal deadline, 5 of 7 revealed is a quorum
+        mock::set_epoch(20);
+        assert!(matches!(reveal(peer(0), 6, true, b"salt6"), Err(Error::WrongVotingPhase)));
+        finalize().unwrap();
+        assert!(is_jailed(&peer(0)));
+        assert!(state().commit_round(&peer(0)).unwrap().is_none());
+        assert!(matches!(finalize(), Err(Error::NotExists)));
+    }
+
+    #[test]
+    fn commitments_are_bound_to_their_voter() {
+        setup(4, commit_reveal());
+        commit(peer(0), 1, true, b"salt").unwrap();
+
+        // voter 2 copies the commitment of voter 1 and replays its reveal
+        mock::set_caller(owner(2));
+        let commitment = ByteBuf::from(report_commitment(&peer(0), &peer(1), true, b"salt").to_vec());
+        A::commit_report(CommitReportPayload { namespace: ns(), checker: peer(0), voter: peer(2), commitment }).unwrap();
+
+        mock::set_epoch(10);
+        reveal(peer(0), 1, true, b"salt").unwrap();
+        assert!(matches!(reveal(peer(0), 2, true, b"salt"), Err(Error::InvalidCommitment)));
+    }
+
+    fn id(checker: PeerID) -> NodeIdPayload {
+        NodeIdPayload { namespace: ns(), id: checker }
+    }
+
+    #[test]
+    fn evictions_wait_for_the_appeal_window() {
+        setup(4, InitParams { appeal_window: Some(10), ..Default::default() });
+        for voter in 1..=3 {
+            report(peer(0), voter).unwrap();
+        }
+        assert!(state().pending_eviction(&peer(0)).unwrap().is_some());
+        assert!(!is_jailed(&peer(0)));
+        assert!(matches!(report(peer(0), 1), Err(Error::EvictionPending)));
+        assert!(matches!(A::finalize_eviction(id(peer(0))), Err(Error::EvictionPending)));
+
+        // the accused appeals and enough checkers saw it online
+        mock::set_caller(owner(1));
+        assert!(matches!(A::appeal_eviction(id(peer(0))), Err(Error::NotOwner)));
+        mock::set_caller(owner(0));
+        A::appeal_eviction(id(peer(0))).unwrap();
+        for voter in 1..=2 {
+            mock::set_caller(owner(voter));
+            A::vouch_online(ReportPayload { namespace: ns(), checker: peer(0), voter: peer(voter) }).unwrap();
+        }
+        mock::set_epoch(10);
+        mock::set_caller(owner(0));
+        assert!(matches!(A::appeal_eviction(id(peer(0))), Err(Error::WrongVotingPhase)));
+        A::finalize_eviction(id(peer(0))).unwrap();
+        assert!(!is_jailed(&peer(0)));
+        assert!(state().pending_eviction(&peer(0)).unwrap().is_none());
+
+        // without an appeal the checker is jailed once the window closes
+        for voter in 1..=3 {
+            report(peer(0), voter).unwrap();
+        }
+        mock::set_epoch(19);
+        assert!(matches!(A::finalize_eviction(id(peer(0))), Err(Error::EvictionPending)));
+        mock::set_epoch(20);
+        A::finalize_eviction(id(peer(0))).unwrap();
+        assert!(is_jailed(&peer(0)));
+        assert!(matches!(A::finalize_eviction(id(peer(0))), Err(Error::NotExists)));
+    }
+
+    #[test]
+    fn jail_cooldowns_double_with_every_strike() {
+        setup(4, InitParams { appeal_window: Some(0), jail_cooldown: Some(100), ..Default::default() });
+        let mut epoch = 0;
+        for cooldown in [100, 200, 400] {
+            for voter in 1..=3 {
+                report(peer(0), voter).unwrap();
+            }
+            assert!(is_jailed(&peer(0)));
+            assert_eq!(state().total_checkers(), 3);
+            assert!(matches!(report(peer(0), 1), Err(Error::Jailed)));
+
+            mock::set_epoch(epoch + cooldown - 1);
+            mock::set_caller(owner(0));
+            assert!(matches!(A::unjail(id(peer(0))), Err(Error::Jailed)));
+            epoch += cooldown;
+            mock::set_epoch(epoch);
+            mock::set_caller(owner(1));
+            assert!(matches!(A::unjail(id(peer(0))), Err(Error::NotOwner)));
+            mock::set_caller(owner(0));
+            A::unjail(id(peer(0))).unwrap();
+            assert!(!is_jailed(&peer(0)));
+            assert_eq!(state().total_checkers(), 4);
+            assert!(matches!(A::unjail(id(peer(0))), Err(Error::NotExists)));
+        }
+    }
+
+    /// Subscribes the actors to method 2, as an admin.
+    fn subscribe(actors: &[ActorID]) {
+        mock::set_caller(owner(0));
+        for actor in actors {
+            A::subscribe(SubscribePayload { namespace: ns(), subscriber: Address::new_id(*actor), method: 2 }).unwrap();
+        }
+    }
+
+    /// The notifications sent to `actor`, in order
+    fn received(sent: &[(Address, MethodNum, RawBytes, TokenAmount)], actor: ActorID) -> Vec<StatusNotification> {
+        sent.iter()
+            .filter(|(to, method, ..)| *to == Address::new_id(actor) && *method == 2)
+            .map(|(_, _, params, _)| params.deserialize().unwrap())
+            .collect()
+    }
+
+    fn subscription(actor: ActorID) -> Subscription {
+        state().subscriptions().unwrap().into_iter().find(|s| s.actor == actor).unwrap()
+    }
+
+    #[test]
+    fn status_changes_are_sent_to_every_subscriber() {
+        setup(4, InitParams { appeal_window: Some(0), ..Default::default() });
+        subscribe(&[300, 301]);
+        mock::with(|m| m.failing.insert(301));
+        for voter in 1..=3 {
+            report(peer(0), voter).unwrap();
+        }
+        // the failing subscriber does not revert the jailing
+        assert!(is_jailed(&peer(0)));
+        let sent = mock::take_sent();
+        let jailed = received(&sent, 300);
+        assert_eq!(jailed.len(), 1);
+        assert_eq!((&jailed[0].id, jailed[0].change, jailed[0].seq), (&peer(0), StatusChange::CheckerJailed, 0));
+        assert!(received(&sent, 301).is_empty());
+        assert!(subscription(300).undelivered.is_empty());
+        assert_eq!(Vec::from(subscription(301).undelivered), jailed);
+
+        // only the subscriber is sent its undelivered notifications again
+        let deliver = || A::deliver_notifications(SubscriberPayload { namespace: ns(), subscriber: Address::new_id(301) });
+        mock::set_caller(owner(0));
+        assert!(matches!(deliver(), Err(Error::NotCaller)));
+        mock::set_caller(301);
+        deliver().unwrap();
+        assert_eq!(subscription(301).undelivered.len(), 1);
+        mock::with(|m| m.failing.clear());
+        deliver().unwrap();
+        assert_eq!(received(&mock::take_sent(), 301), jailed);
+        assert!(subscription(301).undelivered.is_empty());
+    }
+
+    #[test]
+    fn subscribers_are_notified_within_their_gas_limit() {
+        // two votes evict a checker
+        setup(10, InitParams { appeal_window: Some(0), quorum: Some(Quorum::new(1, 10).unwrap()), ..Default::default() });
+        subscribe(&[300, 301]);
+        mock::with(|m| m.gas_used.insert(300, NOTIFICATION_GAS_LIMIT / 2 + 1));
+        for checker in 7..=9 {
+            report(peer(checker), 1).unwrap();
+        }
+        assert!(mock::take_sent().is_empty());
+
+        mock::set_caller(owner(2));
+        A::report_checkers_batch(CheckersReportBatchPayload { namespace: ns(), checkers: (7..=9).map(peer).collect(), voter: peer(2) }).unwrap();
+        let sent = mock::take_sent();
+        let seqs = |ns: &[StatusNotification]| ns.iter().map(|n| n.seq).collect::<Vec<_>>();
+        assert_eq!(seqs(&received(&sent, 301)), vec![0, 1, 2]);
+        // the first two used up the gas limit of the subscriber
+        assert_eq!(seqs(&received(&sent, 300)), vec![0, 1]);
+        assert_eq!(seqs(&Vec::from(subscription(300).undelivered)), vec![2]);
+        assert!(subscription(301).undelivered.is_empty());
     }
 }
@@ -0,0 +1,173 @@
+use crate::runtime;
+use crate::types::PeerID;
+use crate::Error;
+use fvm_shared::clock::ChainEpoch;
+
+/// Domain separation tag for the assignment beacon randomness
+const ASSIGNMENT_RANDOMNESS_DST: i64 = 0x7570; // "up"
+
+/// The first epoch of the assignment window `epoch` falls in.
+pub fn window_start(epoch: ChainEpoch, window: ChainEpoch) -> ChainEpoch {
+    epoch - epoch.rem_euclid(window)
+}
+
+/// Draws the beacon randomness shared by all the assignments of the
+/// window `epoch` falls in. `epoch` must not be in the future.
+pub fn window_randomness(epoch: ChainEpoch, window: ChainEpoch) -> Result<Vec<u8>, Error> {
+    let round = window_start(epoch, window);
+    runtime::beacon_randomness(ASSIGNMENT_RANDOMNESS_DST, round, &[])
+}
+
+fn score(randomness: &[u8], member: &PeerID, checker: &PeerID) -> [u8; 32] {
+    let mut data = Vec::with_capacity(randomness.len() + member.len() + checker.len() + 8);
+    data.extend_from_slice(randomness);
+    data.extend_from_slice(&(member.len() as u64).to_be_bytes());
+    data.extend_from_slice(member.as_bytes());
+    data.extend_from_slice(checker.as_bytes());
+    runtime::hash_blake2b(&data)
+}
+
+/// Picks the `k` checkers in charge of `member` using rendezvous hashing:
+/// every checker is scored with the window randomness and the `k` lowest
+/// scores win. The result does not depend on the order of `checkers`, and
+/// registering or removing a checker only moves the members it wins or loses.
+pub fn assigned_checkers(
+    randomness: &[u8],
+    member: &PeerID,
+    checkers: &[PeerID],
+    k: usize,
+) -> Vec<PeerID> {
+    let mut scored = checkers
+        .iter()
+        .map(|c| (score(randomness, member, c), c))
+        .collect::<Vec<_>>();
+    scored.sort();
+    scored.into_iter().take(k).map(|(_, c)| c.clone()).collect()
+}
+
+/// Tells whether `checker` is among the `k` checkers `assigned_checkers`
+/// picks for `member`, without collecting the checkers: each one is
+/// `add`ed in turn, until it returns false.
+pub struct Rank<'a> {
+    randomness: &'a [u8],
+    member: &'a PeerID,
+    checker: &'a PeerID,
+    score: [u8; 32],
+    k: usize,
+    /// The checkers scoring below `checker`
+    ahead: usize,
+    /// Whether `checker` itself was added
+    registered: bool,
+}
+
+impl<'a> Rank<'a> {
+    pub fn new(randomness: &'a [u8], member: &'a PeerID, checker: &'a PeerID, k: usize) -> Self {
+        let score = score(randomness, member, checker);
+        Self { randomness, member, checker, score, k, ahead: 0, registered: false }
+    }
+
+    /// Scores `other` against `checker`. Returns false once `k` checkers
+    /// score below it, as the rest cannot change the outcome.
+    pub fn add(&mut self, other: &PeerID) -> bool {
+        if other == self.checker {
+            self.registered = true;
+        } else if (score(self.randomness, self.member, other), other) < (self.score, self.checker) {
+            self.ahead += 1;
+        }
+        self.ahead < self.k
+    }
+
+    pub fn is_assigned(&self) -> bool {
+        self.registered && self.ahead < self.k
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(n: usize) -> Vec<PeerID> {
+        (0..n).map(|i| format!("checker-{}", i)).collect()
+    }
+
+    #[test]
+    fn window_start_is_the_first_epoch_of_the_window() {
+        for (epoch, window, start) in [
+            (0, 120, 0),
+            (1, 120, 0),
+            (119, 120, 0),
+            (120, 120, 120),
+            (121, 120, 120),
+            (239, 120, 120),
+            (240, 120, 240),
+            (5, 1, 5),
+            // before genesis, windows still start on multiples
+            (-1, 120, -120),
+            (-120, 120, -120),
+        ] {
+            assert_eq!(window_start(epoch, window), start, "{} in windows of {}", epoch, window);
+        }
+    }
+
+    #[test]
+    fn window_randomness_is_shared_by_the_window() {
+        let first = window_randomness(120, 120).unwrap();
+        assert_eq!(window_randomness(239, 120).unwrap(), first);
+        assert_ne!(window_randomness(240, 120).unwrap(), first);
+        assert_ne!(window_randomness(119, 120).unwrap(), first);
+    }
+
+    #[test]
+    fn assignments_are_deterministic_for_fixed_randomness() {
+        let checkers = ids(20);
+        let member = PeerID::from("member");
+        let assigned = assigned_checkers(b"randomness", &member, &checkers, 3);
+        assert_eq!(assigned.len(), 3);
+        assert_eq!(assigned_checkers(b"randomness", &member, &checkers, 3), assigned);
+
+        // the order of the checkers does not matter
+        let mut reversed = checkers.clone();
+        reversed.reverse();
+        assert_eq!(assigned_checkers(b"randomness", &member, &reversed, 3), assigned);
+
+        // the assignment is a prefix of the larger ones
+        assert_eq!(assigned_checkers(b"randomness", &member, &checkers, 5)[..3], assigned[..]);
+
+        // other windows and members draw other checkers
+        let others = (0..10)
+            .map(|i| assigned_checkers(format!("randomness-{}", i).as_bytes(), &member, &checkers, 3))
+            .collect::<Vec<_>>();
+        assert!(others.iter().any(|a| *a != assigned));
+    }
+
+    #[test]
+    fn k_is_clamped_to_the_checkers() {
+        let checkers = ids(4);
+        let member = PeerID::from("member");
+        let mut assigned = assigned_checkers(b"randomness", &member, &checkers, 10);
+        assigned.sort();
+        assert_eq!(assigned, checkers);
+        assert!(assigned_checkers(b"randomness", &member, &[], 3).is_empty());
+        assert!(assigned_checkers(b"randomness", &member, &checkers, 0).is_empty());
+    }
+
+    #[test]
+    fn rank_agrees_with_assigned_checkers() {
+        let checkers = ids(12);
+        for k in 0..=13 {
+            for m in 0..20 {
+                let member = format!("member-{}", m);
+                let assigned = assigned_checkers(b"randomness", &member, &checkers, k);
+                for checker in checkers.iter().chain([PeerID::from("unregistered")].iter()) {
+                    let mut rank = Rank::new(b"randomness", &member, checker, k);
+                    for c in &checkers {
+                        if !rank.add(c) {
+                            break;
+                        }
+                    }
+                    assert_eq!(rank.is_assigned(), assigned.contains(checker), "{} {} k={}", member, checker, k);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::{anyhow, Result};
+
+/// Flags that take no value
+const SWITCHES: &[&str] = &["hex", "offline", "require-accept", "help"];
+
+/// The parsed command line: the positional arguments, then `--flag value`
+/// pairs and switches in any order. Flags can be repeated.
+pub struct Args {
+    positional: Vec<String>,
+    flags: Vec<(String, String)>,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut positional = vec![];
+        let mut flags = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(flag) if SWITCHES.contains(&flag) => flags.push((flag.to_string(), "true".to_string())),
+                Some(flag) => {
+                    let value = args.next().ok_or_else(|| anyhow!("missing value for --{}", flag))?;
+                    flags.push((flag.to_string(), value));
+                }
+                None => positional.push(arg),
+            }
+        }
+        Ok(Self { positional, flags })
+    }
+
+    pub fn positional(&self, i: usize) -> Option<&str> {
+        self.positional.get(i).map(String::as_str)
+    }
+
+    /// The last value of a flag
+    pub fn value(&self, flag: &str) -> Option<&str> {
+        self.flags.iter().rev().find(|(f, _)| f == flag).map(|(_, v)| v.as_str())
+    }
+
+    pub fn required(&self, flag: &str) -> Result<String> {
+        self.value(flag).map(String::from).ok_or_else(|| anyhow!("missing --{}", flag))
+    }
+
+    /// Every value of a repeated flag
+    pub fn values(&self, flag: &str) -> Vec<String> {
+        self.flags.iter().filter(|(f, _)| f == flag).map(|(_, v)| v.clone()).collect()
+    }
+
+    pub fn is_set(&self, flag: &str) -> bool {
+        self.value(flag).is_some()
+    }
+
+    pub fn flags(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.flags.iter().map(|(f, v)| (f.as_str(), v.as_str()))
+    }
+
+    /// Parses the value of a flag
+    pub fn parsed<T>(&self, flag: &str) -> Result<T>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.required(flag)?
+            .parse()
+            .map_err(|e| anyhow!("invalid --{}: {}", flag, e))
+    }
+
+    /// Parses the value of a flag, if it is set
+    pub fn optional<T>(&self, flag: &str) -> Result<Option<T>>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.value(flag).map(|_| self.parsed(flag)).transpose()
+    }
+}
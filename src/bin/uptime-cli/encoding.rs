@@ -0,0 +1,98 @@
+use anyhow::{anyhow, Result};
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard padded base64, as taken by `lotus chain create-actor`
+pub fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(s: &str) -> Result<Vec<u8>> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow!("invalid hex string {:?}", s));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| anyhow!("invalid hex: {}", e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes padded base64, to check `base64` round trips
+    fn from_base64(s: &str) -> Vec<u8> {
+        let sextets: Vec<u32> = s
+            .bytes()
+            .filter(|c| *c != b'=')
+            .map(|c| BASE64_ALPHABET.iter().position(|a| *a == c).unwrap() as u32)
+            .collect();
+        let mut bytes = vec![];
+        for chunk in sextets.chunks(4) {
+            let n = chunk.iter().enumerate().fold(0, |n, (i, s)| n | s << (18 - 6 * i));
+            bytes.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn base64_matches_the_rfc_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (bytes, encoded) in vectors {
+            assert_eq!(base64(bytes.as_bytes()), encoded);
+        }
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn base64_round_trips() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 0..bytes.len() {
+            let encoded = base64(&bytes[..len]);
+            assert_eq!(encoded.len(), len.div_ceil(3) * 4);
+            assert_eq!(from_base64(&encoded), &bytes[..len]);
+        }
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(hex(&[0x00, 0x0f, 0xa0, 0xff]), "000fa0ff");
+        assert_eq!(from_hex(&hex(&bytes)).unwrap(), bytes);
+        assert_eq!(from_hex("0xDEADbeef").unwrap(), vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(from_hex("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn invalid_hex_is_rejected() {
+        for s in ["0", "abc", "0x0", "zz", "0g", "+1", "é0", "0xx0"] {
+            assert!(from_hex(s).is_err(), "{:?}", s);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Command line tool to encode the actor params and inspect its state.
+//! Run `uptime-cli help` for the usage.
+
+mod args;
+mod encoding;
+
+use anyhow::{anyhow, Result};
+use args::Args;
+use cid::Cid;
+use fvm_shared::address::Address;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::str::FromStr;
+use uptime_checker::client::{
+    load_car, HealthCheck, InitParams, Method, NamespaceReader, NodeEntry, NodeKind, Params, ParamsBuilder,
+    StateReader, DEFAULT_NAMESPACE,
+};
+
+const USAGE: &str = "\
+uptime-cli, encodes the uptime checker actor params and inspects its state
+
+USAGE:
+    uptime-cli init [--config <FILE>] [--checker <ID>,<CREATOR>,<MULTIADDR>...]... [INIT FLAGS]
+        Prints the constructor params, as taken by `lotus chain create-actor`
+
+    uptime-cli params <METHOD> [--namespace <NS>] [FLAGS]
+        Prints the params of a method, given by number or name, e.g. 2 or new_checker
+
+    uptime-cli dump <CAR> [--root <CID>] [--namespace <NS>]
+        Prints the checkers, members and open vote rounds of a state snapshot as JSON
+
+INIT FLAGS, also the keys of the TOML config, see `InitParams`:
+    --namespace, --checkers-per-member, --assignment-window, --voting-duration,
+    --appeal-window, --jail-cooldown, --member-deposit, --max-members,
+    --max-members-per-creator, --hamt-bit-width, --voting-mode <JSON>, --quorum <JSON>
+
+    The TOML config lists the initial checkers as [[checkers]] tables with the
+    `id`, `creator`, `addresses` and optional `health_checks` keys.
+
+METHOD FLAGS:
+    --id, --checker, --member, --voter <PEER_ID>    the nodes the method acts on
+    --address <MULTIADDR>                           repeated, for new_* and edit_*
+    --health-check <JSON>                           repeated, for new_* and edit_*
+    --kind <checker|member>, --new-owner <ADDRESS>, --require-accept
+    --epoch, --epochs <EPOCHS>, --method <METHOD_NUM>
+    --subscriber <ADDRESS>                          for subscribe, unsubscribe and deliver_notifications
+    --offset, --limit <N>                           the member range of get_assignments
+    --offline, --salt <HEX>                         for commit_report and reveal_report
+    --node <ID>,<MULTIADDR>...                      repeated, for register_members_batch
+    --checker <ID>                                  repeated, for report_checkers_batch
+
+OUTPUT:
+    Params are printed in base64, or in hex with --hex.
+";
+
+/// The `InitParams` fields that can be set from flags, as flag names
+const INIT_FLAGS: &[&str] = &[
+    "checkers-per-member",
+    "assignment-window",
+    "voting-duration",
+    "voting-mode",
+    "appeal-window",
+    "jail-cooldown",
+    "member-deposit",
+    "max-members",
+    "max-members-per-creator",
+    "quorum",
+    "hamt-bit-width",
+];
+
+/// The `InitParams` fields that are strings even when they look like numbers
+const STRING_FIELDS: &[&str] = &["namespace", "member_deposit"];
+
+/// An initial checker, as listed in the TOML config
+#[derive(Debug, Deserialize)]
+struct CheckerConfig {
+    id: String,
+    creator: String,
+    addresses: Vec<String>,
+    #[serde(default)]
+    health_checks: Vec<HealthCheck>,
+}
+
+impl FromStr for CheckerConfig {
+    type Err = anyhow::Error;
+
+    /// Parses `<ID>,<CREATOR>,<MULTIADDR>...` as given to `--checker`
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split(',').map(String::from);
+        let (id, creator) = match (parts.next(), parts.next()) {
+            (Some(id), Some(creator)) => (id, creator),
+            _ => return Err(anyhow!("invalid checker {:?}, expected <ID>,<CREATOR>,<MULTIADDR>...", s)),
+        };
+        Ok(Self { id, creator, addresses: parts.collect(), health_checks: vec![] })
+    }
+}
+
+fn main() {
+    let r = Args::parse(std::env::args().skip(1)).and_then(|args| match args.positional(0) {
+        Some("init") => print_params(&args, Method::Init),
+        Some("params") => {
+            let method = args.positional(1).ok_or_else(|| anyhow!("missing <METHOD>"))?;
+            print_params(&args, parse_method(method)?)
+        }
+        Some("dump") => dump(&args),
+        _ => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+    });
+    if let Err(e) = r {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_method(s: &str) -> Result<Method> {
+    let method = match s.parse() {
+        Ok(num) => Method::from_num(num),
+        Err(_) => Method::from_name(s),
+    };
+    method.ok_or_else(|| anyhow!("unknown method {:?}", s))
+}
+
+fn print_params(args: &Args, method: Method) -> Result<()> {
+    let params = encode_params(args, method)?;
+    if args.is_set("hex") {
+        println!("{}", encoding::hex(&params.bytes));
+    } else {
+        println!("{}", encoding::base64(&params.bytes));
+    }
+    Ok(())
+}
+
+fn encode_params(args: &Args, method: Method) -> Result<Params> {
+    let b = ParamsBuilder::new(args.value("namespace").unwrap_or(DEFAULT_NAMESPACE));
+    let params = match method {
+        Method::Init | Method::NewNamespace => {
+            let params = init_params(args)?;
+            let b = ParamsBuilder::new(params.namespace.clone());
+            if method == Method::Init {
+                b.init(params)
+            } else {
+                b.new_namespace(params)
+            }
+        }
+        Method::NewChecker | Method::NewMember | Method::EditChecker | Method::EditMember => {
+            let id = args.required("id")?;
+            let addresses = args.values("address");
+            let health_checks = args
+                .values("health-check")
+                .iter()
+                .map(|h| serde_json::from_str(h).map_err(|e| anyhow!("invalid --health-check: {}", e)))
+                .collect::<Result<Vec<HealthCheck>>>()?;
+            match method {
+                Method::NewChecker => b.new_checker(id, addresses, health_checks),
+                Method::NewMember => b.new_member(id, addresses, health_checks),
+                Method::EditChecker => b.edit_checker(id, addresses, health_checks),
+                _ => b.edit_member(id, addresses, health_checks),
+            }
+        }
+        Method::RmChecker => b.rm_checker(args.required("id")?),
+        Method::RmMember => b.rm_member(args.required("id")?),
+        Method::ReportChecker => b.report_checker(args.required("checker")?, args.required("voter")?),
+        Method::TransferOwnership => {
+            let new_owner = address(args, "new-owner")?;
+            b.transfer_ownership(node_kind(args)?, args.required("id")?, new_owner, args.is_set("require-accept"))
+        }
+        Method::AcceptOwnership => b.accept_ownership(node_kind(args)?, args.required("id")?),
+        Method::ReportMember => b.report_member(args.required("member")?, args.required("voter")?),
+        Method::GetAssignments => b.get_assignments(
+            args.required("checker")?,
+            args.parsed("epoch")?,
+            args.optional("offset")?.unwrap_or(0),
+            args.optional("limit")?,
+        ),
+        Method::CommitReport | Method::RevealReport => {
+            let checker = args.required("checker")?;
+            let voter = args.required("voter")?;
+            let salt = encoding::from_hex(&args.required("salt")?)?;
+            let offline = args.is_set("offline");
+            if method == Method::CommitReport {
+                b.commit_report(checker, voter, offline, &salt)
+            } else {
+                b.reveal_report(checker, voter, offline, &salt)
+            }
+        }
+        Method::FinalizeReport => b.finalize_report(args.required("checker")?),
+        Method::AppealEviction => b.appeal_eviction(args.required("checker")?),
+        Method::VouchOnline => b.vouch_online(args.required("checker")?, args.required("voter")?),
+        Method::FinalizeEviction => b.finalize_eviction(args.required("checker")?),
+        Method::Unjail => b.unjail(args.required("checker")?),
+        Method::Subscribe => b.subscribe(address(args, "subscriber")?, args.parsed("method")?),
+        Method::Unsubscribe => b.unsubscribe(address(args, "subscriber")?),
+        Method::MemberStatus => b.member_status(args.required("member")?),
+        Method::MemberUptime => b.member_uptime(args.required("member")?, args.parsed("epochs")?),
+        Method::CheckerStatus => b.checker_status(args.required("checker")?),
+        Method::Migrate => Ok(ParamsBuilder::migrate()),
+        Method::RegisterMembersBatch => {
+            let members = args.values("node").iter().map(|n| node_entry(n.as_str())).collect::<Result<_>>()?;
+            b.register_members_batch(members)
+        }
+        Method::ReportCheckersBatch => b.report_checkers_batch(args.values("checker"), args.required("voter")?),
+        Method::DeliverNotifications => b.deliver_notifications(address(args, "subscriber")?),
+    };
+    Ok(params?)
+}
+
+/// Parses the address given to `--<flag>`
+fn address(args: &Args, flag: &str) -> Result<Address> {
+    Address::from_str(&args.required(flag)?).map_err(|e| anyhow!("invalid --{}: {}", flag, e))
+}
+
+/// Parses `<ID>,<MULTIADDR>...` as given to `--node`
+fn node_entry(s: &str) -> Result<NodeEntry> {
+    let mut parts = s.split(',').map(String::from);
+    let id = parts.next().filter(|id| !id.is_empty()).ok_or_else(|| anyhow!("invalid node {:?}", s))?;
+    Ok(NodeEntry { id, addresses: parts.collect(), health_checks: vec![] })
+}
+
+fn node_kind(args: &Args) -> Result<NodeKind> {
+    match args.required("kind")?.as_str() {
+        "checker" => Ok(NodeKind::Checker),
+        "member" => Ok(NodeKind::Member),
+        k => Err(anyhow!("invalid --kind {:?}, expected checker or member", k)),
+    }
+}
+
+/// Builds the constructor params from the TOML config, if any, overridden
+/// by the flags, and checks them as the actor would.
+fn init_params(args: &Args) -> Result<InitParams> {
+    let mut fields = match args.value("config") {
+        Some(path) => {
+            let config = std::fs::read_to_string(path).map_err(|e| anyhow!("cannot read {}: {}", path, e))?;
+            match toml::from_str::<Value>(&config).map_err(|e| anyhow!("invalid {}: {}", path, e))? {
+                Value::Object(fields) => fields,
+                _ => return Err(anyhow!("invalid {}: not a table", path)),
+            }
+        }
+        None => Map::new(),
+    };
+
+    let mut checkers: Vec<CheckerConfig> = match fields.remove("checkers") {
+        Some(c) => serde_json::from_value(c).map_err(|e| anyhow!("invalid checkers: {}", e))?,
+        None => vec![],
+    };
+    for c in args.values("checker") {
+        checkers.push(c.parse()?);
+    }
+    set_checkers(&mut fields, checkers);
+
+    for (flag, value) in args.flags() {
+        if flag == "namespace" || INIT_FLAGS.contains(&flag) {
+            let field = flag.replace('-', "_");
+            let value = match serde_json::from_str(value) {
+                Ok(v) if !STRING_FIELDS.contains(&field.as_str()) => v,
+                _ => Value::String(value.to_string()),
+            };
+            fields.insert(field, value);
+        }
+    }
+
+    let params: InitParams =
+        serde_json::from_value(Value::Object(fields)).map_err(|e| anyhow!("invalid init params: {}", e))?;
+    params.validate()?;
+    params.hamt_bit_width()?;
+    Ok(params)
+}
+
+/// Sets the parallel lists of `InitParams` from the initial checkers
+fn set_checkers(fields: &mut Map<String, Value>, checkers: Vec<CheckerConfig>) {
+    let with_health_checks = checkers.iter().any(|c| !c.health_checks.is_empty());
+    fields.insert("ids".into(), checkers.iter().map(|c| json!(c.id)).collect());
+    fields.insert("creators".into(), checkers.iter().map(|c| json!(c.creator)).collect());
+    fields.insert("addresses".into(), checkers.iter().map(|c| json!(c.addresses)).collect());
+    if with_health_checks {
+        fields.insert("health_checks".into(), checkers.iter().map(|c| json!(c.health_checks)).collect());
+    }
+}
+
+fn dump(args: &Args) -> Result<()> {
+    let path = args.positional(1).ok_or_else(|| anyhow!("missing <CAR>"))?;
+    let file = File::open(path).map_err(|e| anyhow!("cannot open {}: {}", path, e))?;
+    let (store, roots) = load_car(BufReader::new(file))?;
+    let root = match args.value("root") {
+        Some(r) => Cid::try_from(r).map_err(|e| anyhow!("invalid --root: {}", e))?,
+        None => *roots.first().ok_or_else(|| anyhow!("{} has no root", path))?,
+    };
+
+    let reader = StateReader::new(store, &root)?;
+    let namespaces = match args.value("namespace") {
+        Some(ns) => vec![ns.to_string()],
+        None => reader.namespaces()?,
+    };
+    let mut out = Map::new();
+    for ns in namespaces {
+        out.insert(ns.clone(), dump_namespace(&reader.namespace(&ns)?)?);
+    }
+    println!("{}", serde_json::to_string_pretty(&json!({ "version": reader.version(), "admins": reader.admins(), "namespaces": out }))?);
+    Ok(())
+}
+
+fn dump_namespace<BS: fvm_ipld_blockstore::Blockstore>(r: &NamespaceReader<BS>) -> Result<Value> {
+    Ok(json!({
+        "checkers_per_member": r.checkers_per_member(),
+        "voting_mode": r.voting_mode(),
+        "quorum": r.quorum(),
+        "hamt_bit_width": r.hamt_bit_width(),
+        "total_checkers": r.total_checkers(),
+        "total_members": r.total_members(),
+        "checkers": r.checkers()?,
+        "members": r.members()?,
+        "offline_checkers": r.offline_votes(NodeKind::Checker)?.into_iter().collect::<BTreeMap<_, _>>(),
+        "offline_members": r.offline_votes(NodeKind::Member)?.into_iter().collect::<BTreeMap<_, _>>(),
+        "commit_rounds": r.commit_rounds()?.into_iter().collect::<BTreeMap<_, _>>(),
+        "pending_evictions": r.pending_evictions()?.into_iter().collect::<BTreeMap<_, _>>(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uptime_checker::client::{NamespaceId, Quorum, VotingMode};
+
+    const PEER: &str = "12D3KooWD3eckifWpRn9wQpMG9R9hX3sD158z7EqHWmweQAJU5SA";
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse(args.iter().map(|a| a.to_string())).unwrap()
+    }
+
+    /// Writes `config` to a temporary file and returns its path
+    fn config(name: &str, config: &str) -> String {
+        let path = std::env::temp_dir().join(format!("uptime-cli-{}-{}.toml", std::process::id(), name));
+        std::fs::write(&path, config).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn config_files_and_flags_give_the_same_init_params() {
+        let path = config(
+            "same",
+            &format!(
+                r#"
+namespace = "lan"
+checkers_per_member = 1
+member_deposit = "1000"
+voting_mode = {{ CommitReveal = {{ commit_duration = 10, reveal_duration = 5 }} }}
+quorum = {{ numerator = 1, denominator = 2 }}
+
+[[checkers]]
+id = "{}"
+creator = "f0100"
+addresses = ["/ip4/127.0.0.1/tcp/1234", "/ip4/127.0.0.1/udp/1234/quic"]
+"#,
+                PEER
+            ),
+        );
+        let from_config = init_params(&args(&["init", "--config", &path]));
+        std::fs::remove_file(&path).unwrap();
+        let from_config = from_config.unwrap();
+
+        let checker = format!("{},f0100,/ip4/127.0.0.1/tcp/1234,/ip4/127.0.0.1/udp/1234/quic", PEER);
+        let from_flags = init_params(&args(&[
+            "init",
+            "--namespace",
+            "lan",
+            "--checker",
+            &checker,
+            "--checkers-per-member",
+            "1",
+            "--member-deposit",
+            "1000",
+            "--voting-mode",
+            r#"{"CommitReveal":{"commit_duration":10,"reveal_duration":5}}"#,
+            "--quorum",
+            r#"{"numerator":1,"denominator":2}"#,
+        ]))
+        .unwrap();
+
+        assert_eq!(from_config, from_flags);
+        assert_eq!(from_flags.namespace, NamespaceId::from("lan"));
+        assert_eq!(from_flags.ids, vec![PEER.to_string()]);
+        assert_eq!(from_flags.creators, vec![Address::new_id(100)]);
+        assert_eq!(from_flags.member_deposit.as_deref(), Some("1000"));
+        assert_eq!(from_flags.voting_mode, VotingMode::CommitReveal { commit_duration: 10, reveal_duration: 5 });
+        assert_eq!(from_flags.quorum, Some(Quorum { numerator: 1, denominator: 2 }));
+    }
+
+    #[test]
+    fn flags_override_the_config_file() {
+        let path = config("override", "checkers_per_member = 3\nmember_deposit = \"1000\"\n");
+        let checker = format!("{},f0100,/ip4/127.0.0.1/tcp/1234", PEER);
+        let params = init_params(&args(&["init", "--config", &path, "--checker", &checker, "--checkers-per-member", "1"]));
+        std::fs::remove_file(&path).unwrap();
+
+        let params = params.unwrap();
+        assert_eq!(params.checkers_per_member, Some(1));
+        assert_eq!(params.member_deposit.as_deref(), Some("1000"));
+        assert_eq!(params.namespace, NamespaceId::from(DEFAULT_NAMESPACE));
+    }
+
+    #[test]
+    fn invalid_init_params_are_rejected() {
+        assert!(init_params(&args(&["init", "--checker", PEER])).is_err());
+        assert!(init_params(&args(&["init", "--quorum", "2"])).is_err());
+        assert!(init_params(&args(&["init", "--config", "/nonexistent/uptime.toml"])).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::anyhow;
+use crate::runtime;
 use crate::Error;
 use cid::multihash::Code;
 use cid::Cid;
@@ -6,15 +7,17 @@ use fvm_ipld_blockstore::Block;
 use fvm_ipld_hamt::{BytesKey, Hamt};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-
-const HAMT_BIT_WIDTH: u32 = 5;
+use std::cell::{Cell, RefCell, RefMut};
+use std::fmt;
+use std::rc::Rc;
+use crate::types::DEFAULT_HAMT_BIT_WIDTH;
 
 /// A blockstore that delegates to IPLD syscalls.
 pub struct Blockstore;
 
 impl fvm_ipld_blockstore::Blockstore for Blockstore {
     fn get(&self, cid: &Cid) -> Result<Option<Vec<u8>>, anyhow::Error> {
-        Ok(Option::from(fvm_sdk::ipld::get(cid)?))
+        Ok(Some(runtime::get_block(cid)?))
     }
 
     fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<(), anyhow::Error> {
@@ -30,8 +33,7 @@ impl fvm_ipld_blockstore::Blockstore for Blockstore {
     where
         D: AsRef<[u8]>,
     {
-        const SIZE: u32 = 32;
-        let k = fvm_sdk::ipld::put(code.into(), SIZE, block.codec, block.data.as_ref())
+        let k = runtime::put_block(code.into(), block.codec, block.data.as_ref())
             .map_err(|e| anyhow!("put failed with {:?}", e))?;
         Ok(k)
     }
@@ -42,19 +44,21 @@ pub type Map<'bs, BS, V> = Hamt<&'bs BS, V, BytesKey>;
 
 /// Create a hamt with a custom bitwidth.
 #[inline]
-pub(crate) fn make_empty_map<BS, V>(store: &'_ BS) -> Map<'_, BS, V>
+pub(crate) fn make_empty_map<BS, V>(store: &'_ BS, bit_width: u32) -> Map<'_, BS, V>
 where
     BS: fvm_ipld_blockstore::Blockstore,
     V: DeserializeOwned + Serialize,
 {
-    Map::<_, V>::new_with_bit_width(store, HAMT_BIT_WIDTH)
+    Map::<_, V>::new_with_bit_width(store, bit_width)
 }
 
-/// Create a map with a root cid.
+/// Create a map with a root cid. The bit width must be the one the map
+/// was created with.
 #[inline]
 pub(crate) fn get_map_from_cid<'bs, BS, V>(
     cid: &Cid,
     store: &'bs BS,
+    bit_width: u32,
 ) -> Result<Map<'bs, BS, V>, Error>
 where
     BS: fvm_ipld_blockstore::Blockstore,
@@ -63,6 +67,120 @@ where
     Ok(Map::<_, V>::load_with_bit_width(
         cid,
         store,
-        HAMT_BIT_WIDTH,
+        bit_width,
     )?)
 }
+
+/// The key of an ActorID, or any u64, in a HAMT: its unsigned varint
+/// encoding, as used by the builtin actors.
+pub(crate) fn u64_key(k: u64) -> BytesKey {
+    BytesKey(varint(k))
+}
+
+/// The unsigned LEB128 varint encoding of `n`
+pub(crate) fn varint(mut n: u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// A HAMT of the state, loaded from its root on first use and kept for
+/// the rest of the invocation, so that each map is read once and flushed
+/// at most once, when the state is saved.
+///
+/// The root it was last flushed to supersedes the one it is given, so that
+/// saving the state again sees the flushed map. Clones share the loaded
+/// map, so that a clone of a state sees its changes, flushed or not.
+pub(crate) struct LazyMap<V> {
+    map: Rc<RefCell<Option<Map<'static, Blockstore, V>>>>,
+    dirty: Rc<Cell<bool>>,
+    flushed: Rc<Cell<Option<Cid>>>,
+    bit_width: u32,
+}
+
+impl<V> Default for LazyMap<V> {
+    fn default() -> Self {
+        Self::new(DEFAULT_HAMT_BIT_WIDTH)
+    }
+}
+
+impl<V> LazyMap<V> {
+    /// An unloaded handle on a map of `bit_width`
+    pub fn new(bit_width: u32) -> Self {
+        Self { map: Rc::default(), dirty: Rc::default(), flushed: Rc::default(), bit_width }
+    }
+}
+
+impl<V> LazyMap<V>
+where
+    V: DeserializeOwned + Serialize,
+{
+    fn loaded(&self, cid: &Cid) -> Result<RefMut<'_, Map<'static, Blockstore, V>>, Error> {
+        let mut map = self.map.borrow_mut();
+        if map.is_none() {
+            let root = self.flushed.get().unwrap_or(*cid);
+            *map = Some(get_map_from_cid(&root, &Blockstore, self.bit_width)?);
+        }
+        Ok(RefMut::map(map, |m| m.as_mut().unwrap()))
+    }
+
+    /// Reads the map rooted at `cid`, loading it on first use.
+    pub fn with<R, F>(&self, cid: &Cid, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&Map<'static, Blockstore, V>) -> Result<R, Error>,
+    {
+        f(&*self.loaded(cid)?)
+    }
+
+    /// Changes the map rooted at `cid`, loading it on first use.
+    pub fn with_mut<R, F>(&self, cid: &Cid, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut Map<'static, Blockstore, V>) -> Result<R, Error>,
+    {
+        self.dirty.set(true);
+        f(&mut *self.loaded(cid)?)
+    }
+
+    /// Flushes the map if it was changed, and returns its root, the root it
+    /// was last flushed to or `cid` otherwise.
+    pub fn flush(&self, cid: &Cid) -> Result<Cid, Error> {
+        match self.map.borrow_mut().as_mut() {
+            Some(map) if self.dirty.get() => {
+                let root = map.flush()?;
+                self.dirty.set(false);
+                self.flushed.set(Some(root));
+                Ok(root)
+            }
+            _ => Ok(self.flushed.get().unwrap_or(*cid)),
+        }
+    }
+}
+
+impl<V> Clone for LazyMap<V> {
+    fn clone(&self) -> Self {
+        Self {
+            map: Rc::clone(&self.map),
+            dirty: Rc::clone(&self.dirty),
+            flushed: Rc::clone(&self.flushed),
+            bit_width: self.bit_width,
+        }
+    }
+}
+
+impl<V> fmt::Debug for LazyMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyMap")
+            .field("loaded", &self.map.borrow().is_some())
+            .field("dirty", &self.dirty.get())
+            .field("flushed", &self.flushed.get())
+            .field("bit_width", &self.bit_width)
+            .finish()
+    }
+}
@@ -0,0 +1,209 @@
+use crate::blockstore::varint;
+use crate::Error;
+use anyhow::anyhow;
+use cid::multihash::{Code, MultihashDigest};
+use cid::Cid;
+use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+/// The multicodec of DAG-CBOR blocks, the only ones holding links
+const DAG_CBOR: u64 = 0x71;
+
+/// The CBOR tag of CIDs in DAG-CBOR
+const CID_TAG: u64 = 42;
+
+/// Caps the length of a section, a CID and its block, read from a CAR
+/// file before it is allocated. The FVM caps blocks at 1MiB.
+const MAX_SECTION_LEN: u64 = 2 << 20;
+
+/// The header of a CARv1 file
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct CarHeader {
+    pub roots: Vec<Cid>,
+    pub version: u64,
+}
+
+/// Reads a CARv1 file into a new blockstore, and returns it with the
+/// roots of the file.
+pub fn load_car<R: Read>(reader: R) -> Result<(MemoryBlockstore, Vec<Cid>), Error> {
+    let store = MemoryBlockstore::new();
+    let roots = load_car_into(&store, reader)?;
+    Ok((store, roots))
+}
+
+/// Reads a CARv1 file into `store` and returns its roots. A CARv1 file is
+/// a varint length prefixed DAG-CBOR header, followed by varint length
+/// prefixed sections made of a CID and its block. Every block is checked
+/// against the hash of its CID before it is stored.
+pub fn load_car_into<BS: Blockstore, R: Read>(store: &BS, mut reader: R) -> Result<Vec<Cid>, Error> {
+    let header = read_section(&mut reader)?.ok_or(Error::CannotDeserialize)?;
+    let header: CarHeader = fvm_ipld_encoding::from_slice(&header)?;
+    if header.version != 1 {
+        return Err(Error::InvalidParams(format!("unsupported CAR version {}", header.version)));
+    }
+
+    while let Some(section) = read_section(&mut reader)? {
+        let mut data = &section[..];
+        let cid = Cid::read_bytes(&mut data).map_err(|e| anyhow!(e))?;
+        verify_block(&cid, data)?;
+        store.put_keyed(&cid, data)?;
+    }
+    Ok(header.roots)
+}
+
+/// Writes the DAG under `root` to a CARv1 file, e.g. the actor state with
+/// every namespace and HAMT block it references. Returns the number of
+/// blocks written. Every block must be in `store`.
+pub fn export_car<BS: Blockstore, W: Write>(store: &BS, root: &Cid, mut writer: W) -> Result<usize, Error> {
+    let header = fvm_ipld_encoding::to_vec(&CarHeader { roots: vec![*root], version: 1 })?;
+    write_section(&mut writer, &[&header])?;
+
+    let mut seen = HashSet::new();
+    let mut stack = vec![*root];
+    while let Some(cid) = stack.pop() {
+        if !seen.insert(cid) {
+            continue;
+        }
+        let block = store
+            .get(&cid)?
+            .ok_or_else(|| Error::Anyhow(anyhow!("block {} not found", cid)))?;
+        if cid.codec() == DAG_CBOR {
+            stack.extend(links(&block)?);
+        }
+        write_section(&mut writer, &[&cid.to_bytes(), &block])?;
+    }
+    writer.flush().map_err(|e| anyhow!(e))?;
+    Ok(seen.len())
+}
+
+/// Checks `data` hashes to the multihash of `cid`.
+pub fn verify_block(cid: &Cid, data: &[u8]) -> Result<(), Error> {
+    let code = Code::try_from(cid.hash().code()).map_err(|e| anyhow!(e.to_string()))?;
+    if code.digest(data) != *cid.hash() {
+        return Err(Error::Anyhow(anyhow!("block does not match its cid {}", cid)));
+    }
+    Ok(())
+}
+
+/// The CIDs linked from a DAG-CBOR block
+pub fn links(block: &[u8]) -> Result<Vec<Cid>, Error> {
+    let mut links = vec![];
+    let mut data = block;
+    scan_item(&mut data, &mut links)?;
+    Ok(links)
+}
+
+/// Skips the CBOR item at the start of `data`, collecting its links.
+fn scan_item(data: &mut &[u8], links: &mut Vec<Cid>) -> Result<(), Error> {
+    let (major, arg) = read_head(data)?;
+    match major {
+        // unsigned and negative integers, simple values and floats
+        0 | 1 | 7 => {}
+        // byte and text strings
+        2 | 3 => {
+            take(data, arg)?;
+        }
+        4 => {
+            for _ in 0..arg {
+                scan_item(data, links)?;
+            }
+        }
+        5 => {
+            for _ in 0..arg * 2 {
+                scan_item(data, links)?;
+            }
+        }
+        6 if arg == CID_TAG => {
+            let (major, len) = read_head(data)?;
+            let bytes = take(data, len)?;
+            // the CID bytes are prefixed by the multibase identity prefix
+            match (major, bytes.split_first()) {
+                (2, Some((0, mut cid))) => links.push(Cid::read_bytes(&mut cid).map_err(|e| anyhow!(e))?),
+                _ => return Err(Error::CannotDeserialize),
+            }
+        }
+        6 => scan_item(data, links)?,
+        _ => return Err(Error::CannotDeserialize),
+    }
+    Ok(())
+}
+
+/// Reads the major type and argument of a CBOR item head. DAG-CBOR has no
+/// indefinite length items.
+fn read_head(data: &mut &[u8]) -> Result<(u8, u64), Error> {
+    let first = take(data, 1)?[0];
+    let (major, info) = (first >> 5, first & 0x1f);
+    let arg = match info {
+        0..=23 => u64::from(info),
+        24..=27 => take(data, 1 << (info - 24))?
+            .iter()
+            .fold(0u64, |n, b| (n << 8) | u64::from(*b)),
+        _ => return Err(Error::CannotDeserialize),
+    };
+    Ok((major, arg))
+}
+
+fn take<'a>(data: &mut &'a [u8], len: u64) -> Result<&'a [u8], Error> {
+    let len = usize::try_from(len).map_err(|_| Error::CannotDeserialize)?;
+    if data.len() < len {
+        return Err(Error::CannotDeserialize);
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+/// Reads a varint length prefixed section, or None at the end of the file.
+fn read_section<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>, Error> {
+    let len = match read_varint(reader)? {
+        Some(len) if len <= MAX_SECTION_LEN => len as usize,
+        Some(len) => return Err(Error::InvalidParams(format!("CAR section of {} bytes", len))),
+        None => return Ok(None),
+    };
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf).map_err(|e| anyhow!(e))?;
+    Ok(Some(buf))
+}
+
+/// Writes the concatenation of `parts` as a varint length prefixed section.
+fn write_section<W: Write>(writer: &mut W, parts: &[&[u8]]) -> Result<(), Error> {
+    let len = parts.iter().map(|p| p.len() as u64).sum::<u64>();
+    writer.write_all(&varint(len)).map_err(|e| anyhow!(e))?;
+    for p in parts {
+        writer.write_all(p).map_err(|e| anyhow!(e))?;
+    }
+    Ok(())
+}
+
+/// Reads an unsigned LEB128 varint, or None if the reader is at its end.
+fn read_varint<R: Read>(reader: &mut R) -> Result<Option<u64>, Error> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let mut byte = [0u8];
+        if reader.read(&mut byte).map_err(|e| anyhow!(e))? == 0 {
+            return if i == 0 { Ok(None) } else { Err(Error::CannotDeserialize) };
+        }
+        value |= u64::from(byte[0] & 0x7f) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(Error::CannotDeserialize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_above_the_cap_are_rejected_before_they_are_read() {
+        let header = fvm_ipld_encoding::to_vec(&CarHeader { roots: vec![], version: 1 }).unwrap();
+        let mut car = vec![];
+        write_section(&mut car, &[&header]).unwrap();
+        car.extend(varint(u64::MAX >> 1));
+
+        assert!(matches!(load_car(&car[..]), Err(Error::InvalidParams(_))));
+    }
+}
@@ -0,0 +1,24 @@
+//! A host-side client for the actor, behind the `client` feature. It
+//! builds the params of every method, and reads the actor state from a
+//! blockstore or a CAR snapshot without going through the FVM.
+
+pub mod car;
+pub mod params;
+pub mod state;
+
+pub use crate::error::Error;
+pub use crate::hamt_state::{HamtState, RootState};
+pub use crate::migration::STATE_VERSION;
+pub use crate::types::{
+    AcceptOwnershipPayload, AssignmentsPage, AssignmentsQuery, CheckersReportBatchPayload, CommitReportPayload,
+    HealthCheck, InitParams, MembersBatchPayload, MultiAddr, NamespaceId, NodeEntry, NodeIdPayload,
+    NodeInfoPayload, NodeKind, PeerID, Quorum, ReportMemberPayload, ReportPayload, RevealReportPayload,
+    StatusChange, StatusNotification, SubscribePayload, SubscriberPayload, TransferOwnershipPayload, VotingMode,
+    DEFAULT_HAMT_BIT_WIDTH, DEFAULT_NAMESPACE, MAX_BATCH_SIZE,
+};
+// the state the readers decode
+pub use crate::types::{CommitRound, NodeInfo, NodeStatus, PendingEviction, Subscription, Votes};
+pub use car::{export_car, load_car, load_car_into, verify_block, CarHeader};
+pub use params::{Method, Params, ParamsBuilder, METHODS};
+pub use state::{NamespaceReader, StateReader};
+pub use uptime_checker_interface as interface;
@@ -0,0 +1,429 @@
+use crate::types::{
+    report_commitment_preimage, AcceptOwnershipPayload, AssignmentsQuery,
+    CheckersReportBatchPayload, CommitReportPayload, HealthCheck, InitParams, MembersBatchPayload,
+    MultiAddr, NamespaceId, NodeEntry, NodeIdPayload, NodeInfoPayload, NodeKind, PeerID, ReportMemberPayload, ReportPayload, RevealReportPayload,
+    SubscribePayload, SubscriberPayload, TransferOwnershipPayload,
+};
+use crate::Error;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::MethodNum;
+use serde::Serialize;
+use serde_bytes::ByteBuf;
+use uptime_checker_interface::{CheckerQuery, MemberQuery, UptimeQuery};
+
+/// The methods of the actor, see `UptimeCheckerActor` for their semantics.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[repr(u64)]
+pub enum Method {
+    Init = 1,
+    NewChecker = 2,
+    NewMember = 3,
+    EditChecker = 4,
+    EditMember = 5,
+    RmChecker = 6,
+    RmMember = 7,
+    ReportChecker = 8,
+    TransferOwnership = 9,
+    AcceptOwnership = 10,
+    ReportMember = 11,
+    GetAssignments = 12,
+    CommitReport = 13,
+    RevealReport = 14,
+    FinalizeReport = 15,
+    AppealEviction = 16,
+    VouchOnline = 17,
+    FinalizeEviction = 18,
+    Unjail = 19,
+    Subscribe = 20,
+    Unsubscribe = 21,
+    MemberStatus = 22,
+    MemberUptime = 23,
+    CheckerStatus = 24,
+    NewNamespace = 25,
+    Migrate = 26,
+    RegisterMembersBatch = 27,
+    ReportCheckersBatch = 28,
+    DeliverNotifications = 29,
+}
+
+/// Every method, in method number order
+pub const METHODS: [Method; 29] = [
+    Method::Init,
+    Method::NewChecker,
+    Method::NewMember,
+    Method::EditChecker,
+    Method::EditMember,
+    Method::RmChecker,
+    Method::RmMember,
+    Method::ReportChecker,
+    Method::TransferOwnership,
+    Method::AcceptOwnership,
+    Method::ReportMember,
+    Method::GetAssignments,
+    Method::CommitReport,
+    Method::RevealReport,
+    Method::FinalizeReport,
+    Method::AppealEviction,
+    Method::VouchOnline,
+    Method::FinalizeEviction,
+    Method::Unjail,
+    Method::Subscribe,
+    Method::Unsubscribe,
+    Method::MemberStatus,
+    Method::MemberUptime,
+    Method::CheckerStatus,
+    Method::NewNamespace,
+    Method::Migrate,
+    Method::RegisterMembersBatch,
+    Method::ReportCheckersBatch,
+    Method::DeliverNotifications,
+];
+
+impl Method {
+    pub fn num(self) -> MethodNum {
+        self as MethodNum
+    }
+
+    pub fn from_num(num: MethodNum) -> Option<Self> {
+        METHODS.iter().copied().find(|m| m.num() == num)
+    }
+
+    /// The snake case name of the method, as in `UptimeCheckerActor`
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Init => "init",
+            Method::NewChecker => "new_checker",
+            Method::NewMember => "new_member",
+            Method::EditChecker => "edit_checker",
+            Method::EditMember => "edit_member",
+            Method::RmChecker => "rm_checker",
+            Method::RmMember => "rm_member",
+            Method::ReportChecker => "report_checker",
+            Method::TransferOwnership => "transfer_ownership",
+            Method::AcceptOwnership => "accept_ownership",
+            Method::ReportMember => "report_member",
+            Method::GetAssignments => "get_assignments",
+            Method::CommitReport => "commit_report",
+            Method::RevealReport => "reveal_report",
+            Method::FinalizeReport => "finalize_report",
+            Method::AppealEviction => "appeal_eviction",
+            Method::VouchOnline => "vouch_online",
+            Method::FinalizeEviction => "finalize_eviction",
+            Method::Unjail => "unjail",
+            Method::Subscribe => "subscribe",
+            Method::Unsubscribe => "unsubscribe",
+            Method::MemberStatus => "member_status",
+            Method::MemberUptime => "member_uptime",
+            Method::CheckerStatus => "checker_status",
+            Method::NewNamespace => "new_namespace",
+            Method::Migrate => "migrate",
+            Method::RegisterMembersBatch => "register_members_batch",
+            Method::ReportCheckersBatch => "report_checkers_batch",
+            Method::DeliverNotifications => "deliver_notifications",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        METHODS.iter().copied().find(|m| m.name() == name)
+    }
+
+    /// Whether the params and return values are CBOR encoded. The other
+    /// methods take JSON params and return CBOR values, if any.
+    pub fn is_cbor(self) -> bool {
+        matches!(self, Method::MemberStatus | Method::MemberUptime | Method::CheckerStatus)
+    }
+}
+
+/// The encoded params of a message to the actor
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Params {
+    pub method: Method,
+    pub bytes: Vec<u8>,
+}
+
+impl Params {
+    fn json<T: Serialize>(method: Method, params: &T) -> Result<Self, Error> {
+        let bytes = serde_json::to_vec(params).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        Ok(Self { method, bytes })
+    }
+
+    fn cbor<T: Serialize>(method: Method, params: &T) -> Result<Self, Error> {
+        Ok(Self { method, bytes: fvm_ipld_encoding::to_vec(params)? })
+    }
+}
+
+/// Builds the params of the methods acting on a namespace.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParamsBuilder {
+    namespace: NamespaceId,
+}
+
+impl ParamsBuilder {
+    pub fn new(namespace: impl Into<NamespaceId>) -> Self {
+        Self { namespace: namespace.into() }
+    }
+
+    pub fn namespace(&self) -> &NamespaceId {
+        &self.namespace
+    }
+
+    fn ns(&self) -> NamespaceId {
+        self.namespace.clone()
+    }
+
+    fn node_id(&self, method: Method, id: PeerID) -> Result<Params, Error> {
+        Params::json(method, &NodeIdPayload { namespace: self.ns(), id })
+    }
+
+    fn subscriber(&self, method: Method, subscriber: Address) -> Result<Params, Error> {
+        Params::json(method, &SubscriberPayload { namespace: self.ns(), subscriber })
+    }
+
+    fn node_info(
+        &self,
+        method: Method,
+        id: PeerID,
+        addresses: Vec<MultiAddr>,
+        health_checks: Vec<HealthCheck>,
+    ) -> Result<Params, Error> {
+        Params::json(method, &NodeInfoPayload::new(self.ns(), id, addresses, health_checks))
+    }
+
+    /// The constructor params, creating the builder's namespace.
+    pub fn init(&self, params: InitParams) -> Result<Params, Error> {
+        Params::json(Method::Init, &InitParams { namespace: self.ns(), ..params })
+    }
+
+    pub fn new_namespace(&self, params: InitParams) -> Result<Params, Error> {
+        Params::json(Method::NewNamespace, &InitParams { namespace: self.ns(), ..params })
+    }
+
+    pub fn new_checker(
+        &self,
+        id: PeerID,
+        addresses: Vec<MultiAddr>,
+        health_checks: Vec<HealthCheck>,
+    ) -> Result<Params, Error> {
+        self.node_info(Method::NewChecker, id, addresses, health_checks)
+    }
+
+    /// The message must also carry the member deposit as value.
+    pub fn new_member(
+        &self,
+        id: PeerID,
+        addresses: Vec<MultiAddr>,
+        health_checks: Vec<HealthCheck>,
+    ) -> Result<Params, Error> {
+        self.node_info(Method::NewMember, id, addresses, health_checks)
+    }
+
+    pub fn edit_checker(
+        &self,
+        id: PeerID,
+        addresses: Vec<MultiAddr>,
+        health_checks: Vec<HealthCheck>,
+    ) -> Result<Params, Error> {
+        self.node_info(Method::EditChecker, id, addresses, health_checks)
+    }
+
+    pub fn edit_member(
+        &self,
+        id: PeerID,
+        addresses: Vec<MultiAddr>,
+        health_checks: Vec<HealthCheck>,
+    ) -> Result<Params, Error> {
+        self.node_info(Method::EditMember, id, addresses, health_checks)
+    }
+
+    pub fn rm_checker(&self, id: PeerID) -> Result<Params, Error> {
+        self.node_id(Method::RmChecker, id)
+    }
+
+    pub fn rm_member(&self, id: PeerID) -> Result<Params, Error> {
+        self.node_id(Method::RmMember, id)
+    }
+
+    pub fn report_checker(&self, checker: PeerID, voter: PeerID) -> Result<Params, Error> {
+        Params::json(Method::ReportChecker, &ReportPayload { namespace: self.ns(), checker, voter })
+    }
+
+    pub fn transfer_ownership(
+        &self,
+        kind: NodeKind,
+        id: PeerID,
+        new_owner: Address,
+        require_accept: bool,
+    ) -> Result<Params, Error> {
+        Params::json(
+            Method::TransferOwnership,
+            &TransferOwnershipPayload { namespace: self.ns(), id, kind, new_owner, require_accept },
+        )
+    }
+
+    pub fn accept_ownership(&self, kind: NodeKind, id: PeerID) -> Result<Params, Error> {
+        Params::json(Method::AcceptOwnership, &AcceptOwnershipPayload { namespace: self.ns(), id, kind })
+    }
+
+    pub fn report_member(&self, member: PeerID, voter: PeerID) -> Result<Params, Error> {
+        Params::json(Method::ReportMember, &ReportMemberPayload { namespace: self.ns(), member, voter })
+    }
+
+    /// The page of assignments from `offset`, see `AssignmentsPage::next`
+    /// for the following one. The actor caps `limit`.
+    pub fn get_assignments(
+        &self,
+        checker: PeerID,
+        epoch: ChainEpoch,
+        offset: u64,
+        limit: Option<u64>,
+    ) -> Result<Params, Error> {
+        Params::json(
+            Method::GetAssignments,
+            &AssignmentsQuery { namespace: self.ns(), checker, epoch, offset, limit },
+        )
+    }
+
+    /// Commits to `offline` on `checker`. The same `salt` must be kept
+    /// for `reveal_report`.
+    pub fn commit_report(
+        &self,
+        checker: PeerID,
+        voter: PeerID,
+        offline: bool,
+        salt: &[u8],
+    ) -> Result<Params, Error> {
+        let preimage = report_commitment_preimage(&checker, &voter, offline, salt);
+        let commitment = blake2b_simd::Params::new().hash_length(32).hash(&preimage);
+        Params::json(
+            Method::CommitReport,
+            &CommitReportPayload {
+                namespace: self.ns(),
+                checker,
+                voter,
+                commitment: ByteBuf::from(commitment.as_bytes()),
+            },
+        )
+    }
+
+    pub fn reveal_report(
+        &self,
+        checker: PeerID,
+        voter: PeerID,
+        offline: bool,
+        salt: &[u8],
+    ) -> Result<Params, Error> {
+        Params::json(
+            Method::RevealReport,
+            &RevealReportPayload {
+                namespace: self.ns(),
+                checker,
+                voter,
+                offline,
+                salt: ByteBuf::from(salt),
+            },
+        )
+    }
+
+    pub fn finalize_report(&self, checker: PeerID) -> Result<Params, Error> {
+        self.node_id(Method::FinalizeReport, checker)
+    }
+
+    pub fn appeal_eviction(&self, checker: PeerID) -> Result<Params, Error> {
+        self.node_id(Method::AppealEviction, checker)
+    }
+
+    pub fn vouch_online(&self, checker: PeerID, voter: PeerID) -> Result<Params, Error> {
+        Params::json(Method::VouchOnline, &ReportPayload { namespace: self.ns(), checker, voter })
+    }
+
+    pub fn finalize_eviction(&self, checker: PeerID) -> Result<Params, Error> {
+        self.node_id(Method::FinalizeEviction, checker)
+    }
+
+    pub fn unjail(&self, checker: PeerID) -> Result<Params, Error> {
+        self.node_id(Method::Unjail, checker)
+    }
+
+    pub fn subscribe(&self, subscriber: Address, method: MethodNum) -> Result<Params, Error> {
+        Params::json(Method::Subscribe, &SubscribePayload { namespace: self.ns(), subscriber, method })
+    }
+
+    pub fn unsubscribe(&self, subscriber: Address) -> Result<Params, Error> {
+        self.subscriber(Method::Unsubscribe, subscriber)
+    }
+
+    pub fn member_status(&self, member: PeerID) -> Result<Params, Error> {
+        Params::cbor(Method::MemberStatus, &MemberQuery { namespace: self.ns(), member })
+    }
+
+    pub fn member_uptime(&self, member: PeerID, epochs: ChainEpoch) -> Result<Params, Error> {
+        Params::cbor(Method::MemberUptime, &UptimeQuery { namespace: self.ns(), member, epochs })
+    }
+
+    pub fn checker_status(&self, checker: PeerID) -> Result<Params, Error> {
+        Params::cbor(Method::CheckerStatus, &CheckerQuery { namespace: self.ns(), checker })
+    }
+
+    /// The message must also carry the member deposit times the number
+    /// of members as value.
+    pub fn register_members_batch(&self, members: Vec<NodeEntry>) -> Result<Params, Error> {
+        Params::json(Method::RegisterMembersBatch, &MembersBatchPayload { namespace: self.ns(), members })
+    }
+
+    pub fn report_checkers_batch(&self, checkers: Vec<PeerID>, voter: PeerID) -> Result<Params, Error> {
+        Params::json(
+            Method::ReportCheckersBatch,
+            &CheckersReportBatchPayload { namespace: self.ns(), checkers, voter },
+        )
+    }
+
+    pub fn deliver_notifications(&self, subscriber: Address) -> Result<Params, Error> {
+        self.subscriber(Method::DeliverNotifications, subscriber)
+    }
+
+    /// `migrate` takes no params and acts on every namespace.
+    pub fn migrate() -> Params {
+        Params { method: Method::Migrate, bytes: vec![] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::report_commitment;
+
+    #[test]
+    fn commitments_are_the_ones_the_actor_checks() {
+        let b = ParamsBuilder::new("rpc");
+        let (checker, voter) = (PeerID::from("checker"), PeerID::from("voter"));
+        for (offline, salt) in [(true, &b"salt"[..]), (false, &b"salt"[..]), (true, &[][..]), (false, &[0u8; 64][..])] {
+            let params = b.commit_report(checker.clone(), voter.clone(), offline, salt).unwrap();
+            assert_eq!(params.method, Method::CommitReport);
+            let payload: CommitReportPayload = serde_json::from_slice(&params.bytes).unwrap();
+            assert_eq!((payload.namespace.as_str(), &payload.checker, &payload.voter), ("rpc", &checker, &voter));
+            assert_eq!(payload.commitment.as_slice(), report_commitment(&checker, &voter, offline, salt));
+        }
+    }
+
+    #[test]
+    fn methods_match_the_actor_dispatch() {
+        // the `num => Actor::<HamtState>::name(` arms of `invoke`
+        let dispatched: Vec<(MethodNum, &str)> = include_str!("../lib.rs")
+            .lines()
+            .filter_map(|line| {
+                let (num, call) = line.trim().split_once(" => Actor::<HamtState>::")?;
+                Some((num.parse().ok()?, call.split('(').next()?))
+            })
+            .collect();
+        assert_eq!(dispatched.len(), METHODS.len());
+        for (num, name) in dispatched {
+            let method = Method::from_num(num).unwrap_or_else(|| panic!("no method {}", num));
+            assert_eq!(method.name(), name, "method {}", num);
+            assert_eq!(Method::from_name(name), Some(method));
+        }
+        for (i, method) in METHODS.iter().enumerate() {
+            assert_eq!(method.num(), i as MethodNum + 1);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::blockstore::get_map_from_cid;
+use crate::hamt_state::{HamtState, RootState};
+use crate::migration::STATE_VERSION;
+use crate::types::{
+    CommitRound, NamespaceId, NodeInfo, NodeKind, PeerID, PendingEviction, Quorum, Subscription,
+    Votes, VotingMode, DEFAULT_HAMT_BIT_WIDTH,
+};
+use crate::Error;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+use fvm_ipld_hamt::BytesKey;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::ActorID;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Reads the actor state from a blockstore holding its blocks, e.g. one
+/// loaded from a CAR snapshot with `car::load_car`.
+pub struct StateReader<BS> {
+    store: BS,
+    root: RootState,
+}
+
+impl<BS: Blockstore> StateReader<BS> {
+    /// Loads the root state object at `root`, the actor's state CID. Only
+    /// the current layout can be read, see `STATE_VERSION`.
+    pub fn new(store: BS, root: &Cid) -> Result<Self, Error> {
+        let root = store.get_cbor::<RootState>(root)?.ok_or(Error::NotExists)?;
+        if root.version != STATE_VERSION {
+            return Err(Error::StateVersion(root.version));
+        }
+        Ok(Self { store, root })
+    }
+
+    pub fn version(&self) -> u64 {
+        self.root.version
+    }
+
+    /// The actors managing the namespaces and subscribers
+    pub fn admins(&self) -> &[ActorID] {
+        &self.root.admins
+    }
+
+    pub fn store(&self) -> &BS {
+        &self.store
+    }
+
+    /// The ids of the namespaces of the actor
+    pub fn namespaces(&self) -> Result<Vec<NamespaceId>, Error> {
+        Ok(entries::<_, HamtState>(&self.root.namespaces, &self.store, DEFAULT_HAMT_BIT_WIDTH)?
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect())
+    }
+
+    pub fn namespace(&self, namespace: &str) -> Result<NamespaceReader<'_, BS>, Error> {
+        let map = get_map_from_cid::<_, HamtState>(&self.root.namespaces, &self.store, DEFAULT_HAMT_BIT_WIDTH)?;
+        let mut state = map.get(&BytesKey::from(namespace))?.ok_or(Error::NotExists)?.clone();
+        state.namespace = namespace.to_string();
+        Ok(NamespaceReader { store: &self.store, state })
+    }
+}
+
+/// Reads the registry of a namespace
+pub struct NamespaceReader<'a, BS> {
+    store: &'a BS,
+    state: HamtState,
+}
+
+impl<'a, BS: Blockstore> NamespaceReader<'a, BS> {
+    pub fn namespace(&self) -> &NamespaceId {
+        &self.state.namespace
+    }
+
+    /// The decoded namespace state, its HAMTs are read with the other
+    /// methods.
+    pub fn state(&self) -> &HamtState {
+        &self.state
+    }
+
+    pub fn nodes(&self, kind: NodeKind) -> Result<Vec<NodeInfo>, Error> {
+        let map_cid = match kind {
+            NodeKind::Checker => &self.state.checkers,
+            NodeKind::Member => &self.state.members,
+        };
+        self.values(map_cid)
+    }
+
+    pub fn checkers(&self) -> Result<Vec<NodeInfo>, Error> {
+        self.nodes(NodeKind::Checker)
+    }
+
+    pub fn members(&self) -> Result<Vec<NodeInfo>, Error> {
+        self.nodes(NodeKind::Member)
+    }
+
+    /// The open offline votes on the nodes of `kind`, by reported PeerID
+    pub fn offline_votes(&self, kind: NodeKind) -> Result<Vec<(PeerID, Votes)>, Error> {
+        let map_cid = match kind {
+            NodeKind::Checker => &self.state.offline_checkers,
+            NodeKind::Member => &self.state.offline_members,
+        };
+        self.entries(map_cid)
+    }
+
+    /// The open commit-reveal rounds, by reported checker
+    pub fn commit_rounds(&self) -> Result<Vec<(PeerID, CommitRound)>, Error> {
+        self.entries(&self.state.vote_commits)
+    }
+
+    pub fn pending_evictions(&self) -> Result<Vec<(PeerID, PendingEviction)>, Error> {
+        self.entries(&self.state.pending_evictions)
+    }
+
+    /// The number of members registered by each creator
+    pub fn member_counts(&self) -> Result<Vec<(ActorID, u64)>, Error> {
+        raw_entries::<_, u64>(&self.state.member_counts, self.store, self.state.hamt_bit_width)?
+            .into_iter()
+            .map(|(k, v)| Ok((parse_u64_key(&k)?, v)))
+            .collect()
+    }
+
+    pub fn subscribers(&self) -> Result<Vec<Subscription>, Error> {
+        self.values(&self.state.subscribers)
+    }
+
+    pub fn total_checkers(&self) -> usize {
+        self.state.total_checkers
+    }
+
+    pub fn total_members(&self) -> u64 {
+        self.state.total_members
+    }
+
+    pub fn voting_duration(&self) -> ChainEpoch {
+        self.state.voting_duration
+    }
+
+    pub fn voting_mode(&self) -> &VotingMode {
+        &self.state.voting_mode
+    }
+
+    pub fn quorum(&self) -> &Quorum {
+        &self.state.quorum
+    }
+
+    pub fn checkers_per_member(&self) -> u64 {
+        self.state.checkers_per_member
+    }
+
+    pub fn assignment_window(&self) -> ChainEpoch {
+        self.state.assignment_window
+    }
+
+    pub fn appeal_window(&self) -> ChainEpoch {
+        self.state.appeal_window
+    }
+
+    pub fn jail_cooldown(&self) -> ChainEpoch {
+        self.state.jail_cooldown
+    }
+
+    pub fn member_deposit(&self) -> &TokenAmount {
+        &self.state.member_deposit
+    }
+
+    pub fn max_members(&self) -> Option<u64> {
+        self.state.max_members
+    }
+
+    pub fn max_members_per_creator(&self) -> Option<u64> {
+        self.state.max_members_per_creator
+    }
+
+    pub fn hamt_bit_width(&self) -> u32 {
+        self.state.hamt_bit_width
+    }
+
+    fn entries<V>(&self, map_cid: &Cid) -> Result<Vec<(String, V)>, Error>
+    where
+        V: DeserializeOwned + Serialize + Clone,
+    {
+        entries(map_cid, self.store, self.state.hamt_bit_width)
+    }
+
+    /// The values of a HAMT, whatever its keys
+    fn values<V>(&self, map_cid: &Cid) -> Result<Vec<V>, Error>
+    where
+        V: DeserializeOwned + Serialize + Clone,
+    {
+        Ok(raw_entries(map_cid, self.store, self.state.hamt_bit_width)?
+            .into_iter()
+            .map(|(_, v)| v)
+            .collect())
+    }
+}
+
+/// The entries of a HAMT, with their raw keys
+fn raw_entries<BS, V>(map_cid: &Cid, store: &BS, bit_width: u32) -> Result<Vec<(Vec<u8>, V)>, Error>
+where
+    BS: Blockstore,
+    V: DeserializeOwned + Serialize + Clone,
+{
+    let map = get_map_from_cid::<_, V>(map_cid, store, bit_width)?;
+    let mut entries = vec![];
+    map.for_each(|k, v| {
+        entries.push((k.0.clone(), v.clone()));
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+/// The entries of a HAMT keyed by strings
+fn entries<BS, V>(map_cid: &Cid, store: &BS, bit_width: u32) -> Result<Vec<(String, V)>, Error>
+where
+    BS: Blockstore,
+    V: DeserializeOwned + Serialize + Clone,
+{
+    raw_entries(map_cid, store, bit_width)?
+        .into_iter()
+        .map(|(k, v)| Ok((String::from_utf8(k).map_err(|e| anyhow::anyhow!(e))?, v)))
+        .collect()
+}
+
+/// Decodes a key written by `blockstore::u64_key`.
+fn parse_u64_key(key: &[u8]) -> Result<u64, Error> {
+    let mut value = 0u64;
+    for (i, byte) in key.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            if i + 1 != key.len() {
+                break;
+            }
+            return Ok(value);
+        }
+    }
+    Err(Error::CannotDeserialize)
+}
@@ -1,8 +1,14 @@
+use fvm_shared::address::Address;
+use fvm_shared::error::ExitCode;
 use fvm_shared::ActorID;
+use std::fmt;
+use crate::p2p::MAX_ADDRESSES;
+use crate::types::{MultiAddr, PeerID};
 
 /// All the error from the actor crate
+#[derive(Debug)]
 pub enum Error {
-    AlreadyVoted(ActorID),
+    AlreadyVoted(PeerID),
     CannotDeserialize,
     FVMIpldHamt(fvm_ipld_hamt::Error),
     Anyhow(anyhow::Error),
@@ -10,37 +16,166 @@ pub enum Error {
     FVMSDKNoState(fvm_sdk::error::NoStateError),
     FVMEncoding(fvm_ipld_encoding::Error),
     FVMSharedAddress(fvm_shared::address::Error),
+    UnresolvedAddress(Address),
     NotOwner,
     NotExists,
     NotCaller,
+    InvalidQuorum,
+    InvalidPeerID(PeerID),
+    InvalidMultiAddr(MultiAddr),
+    TooManyAddresses,
+    InvalidHealthCheck(String),
+    NotAssigned,
+    FutureEpoch,
+    InvalidParams(String),
+    WrongVotingPhase,
+    InvalidCommitment,
+    EvictionPending,
+    Jailed,
+    AlreadyExists,
+    InsufficientDeposit,
+    MemberCapReached,
+    CreatorCapReached(ActorID),
+    RefundFailed,
+    TooManySubscribers,
+    StateVersion(u64),
+    /// The items of a batch that failed, by index. The whole batch is
+    /// rejected if any item fails.
+    BatchFailed(Vec<(usize, Error)>),
 }
 
 impl Error {
+    /// The exit code the actor aborts with, one of the standard user
+    /// exit codes. It only depends on the variant, as listed in
+    /// `uptime_checker_interface::errors::CATALOGUE`.
     pub fn code(&self) -> u32 {
+        let code = match self {
+            Error::AlreadyVoted(_) => ExitCode::USR_FORBIDDEN,
+            Error::CannotDeserialize => ExitCode::USR_SERIALIZATION,
+            Error::FVMIpldHamt(_) => ExitCode::USR_ILLEGAL_STATE,
+            Error::Anyhow(_) => ExitCode::USR_ILLEGAL_STATE,
+            // the error number is in the message, each name keeps a
+            // single code so that the catalogue holds
+            Error::FVMSharedErrorNum(_) => ExitCode::USR_UNSPECIFIED,
+            Error::FVMSDKNoState(_) => ExitCode::USR_ILLEGAL_STATE,
+            Error::FVMEncoding(_) => ExitCode::USR_SERIALIZATION,
+            Error::FVMSharedAddress(_) => ExitCode::USR_ILLEGAL_ARGUMENT,
+            Error::UnresolvedAddress(_) => ExitCode::USR_NOT_FOUND,
+            Error::NotOwner => ExitCode::USR_FORBIDDEN,
+            Error::NotExists => ExitCode::USR_NOT_FOUND,
+            Error::NotCaller => ExitCode::USR_FORBIDDEN,
+            Error::InvalidQuorum => ExitCode::USR_ILLEGAL_ARGUMENT,
+            Error::InvalidPeerID(_) => ExitCode::USR_ILLEGAL_ARGUMENT,
+            Error::InvalidMultiAddr(_) => ExitCode::USR_ILLEGAL_ARGUMENT,
+            Error::TooManyAddresses => ExitCode::USR_ILLEGAL_ARGUMENT,
+            Error::InvalidHealthCheck(_) => ExitCode::USR_ILLEGAL_ARGUMENT,
+            Error::NotAssigned => ExitCode::USR_FORBIDDEN,
+            Error::FutureEpoch => ExitCode::USR_ILLEGAL_ARGUMENT,
+            Error::InvalidParams(_) => ExitCode::USR_ILLEGAL_ARGUMENT,
+            Error::WrongVotingPhase => ExitCode::USR_ILLEGAL_STATE,
+            Error::InvalidCommitment => ExitCode::USR_ILLEGAL_ARGUMENT,
+            Error::EvictionPending => ExitCode::USR_ILLEGAL_STATE,
+            Error::Jailed => ExitCode::USR_ILLEGAL_STATE,
+            Error::AlreadyExists => ExitCode::USR_ILLEGAL_ARGUMENT,
+            Error::InsufficientDeposit => ExitCode::USR_INSUFFICIENT_FUNDS,
+            Error::MemberCapReached => ExitCode::USR_ILLEGAL_STATE,
+            Error::CreatorCapReached(_) => ExitCode::USR_ILLEGAL_STATE,
+            Error::RefundFailed => ExitCode::USR_ILLEGAL_STATE,
+            Error::TooManySubscribers => ExitCode::USR_ILLEGAL_STATE,
+            Error::StateVersion(_) => ExitCode::USR_ILLEGAL_STATE,
+            Error::BatchFailed(_) => ExitCode::USR_ILLEGAL_ARGUMENT,
+        };
+        code.value()
+    }
+
+    /// The stable name of the error, listed in the catalogue.
+    pub fn name(&self) -> &'static str {
         match self {
-            Error::AlreadyVoted(_) => 10002,
-            Error::CannotDeserialize => 10003,
-            Error::FVMIpldHamt(_) => 10004,
-            Error::Anyhow(_) => 10005,
-            Error::FVMSharedErrorNum(e) => *e as u32,
-            Error::FVMSDKNoState(_) => 10006,
-            Error::FVMEncoding(_) => 10007,
-            Error::FVMSharedAddress(_) => 10008,
-            Error::NotOwner => 10009,
-            Error::NotExists => 10010,
-            Error::NotCaller => 10011,
+            Error::AlreadyVoted(_) => "AlreadyVoted",
+            Error::CannotDeserialize => "CannotDeserialize",
+            Error::FVMIpldHamt(_) => "FVMIpldHamt",
+            Error::Anyhow(_) => "Anyhow",
+            Error::FVMSharedErrorNum(_) => "FVMSharedErrorNum",
+            Error::FVMSDKNoState(_) => "FVMSDKNoState",
+            Error::FVMEncoding(_) => "FVMEncoding",
+            Error::FVMSharedAddress(_) => "FVMSharedAddress",
+            Error::UnresolvedAddress(_) => "UnresolvedAddress",
+            Error::NotOwner => "NotOwner",
+            Error::NotExists => "NotExists",
+            Error::NotCaller => "NotCaller",
+            Error::InvalidQuorum => "InvalidQuorum",
+            Error::InvalidPeerID(_) => "InvalidPeerID",
+            Error::InvalidMultiAddr(_) => "InvalidMultiAddr",
+            Error::TooManyAddresses => "TooManyAddresses",
+            Error::InvalidHealthCheck(_) => "InvalidHealthCheck",
+            Error::NotAssigned => "NotAssigned",
+            Error::FutureEpoch => "FutureEpoch",
+            Error::InvalidParams(_) => "InvalidParams",
+            Error::WrongVotingPhase => "WrongVotingPhase",
+            Error::InvalidCommitment => "InvalidCommitment",
+            Error::EvictionPending => "EvictionPending",
+            Error::Jailed => "Jailed",
+            Error::AlreadyExists => "AlreadyExists",
+            Error::InsufficientDeposit => "InsufficientDeposit",
+            Error::MemberCapReached => "MemberCapReached",
+            Error::CreatorCapReached(_) => "CreatorCapReached",
+            Error::RefundFailed => "RefundFailed",
+            Error::TooManySubscribers => "TooManySubscribers",
+            Error::StateVersion(_) => "StateVersion",
+            Error::BatchFailed(_) => "BatchFailed",
         }
     }
 
     pub fn msg(&self) -> String {
         match self {
-            Error::FVMSharedAddress(e) => format!("{:?}", e),
-            Error::AlreadyVoted(a) => format!("actor {:?} already voted", a),
-            _ => String::from("")
+            Error::AlreadyVoted(a) => format!("checker {:?} already voted", a),
+            Error::CannotDeserialize => String::from("cannot deserialize"),
+            Error::FVMIpldHamt(e) => format!("hamt: {}", e),
+            Error::Anyhow(e) => format!("{:#}", e),
+            Error::FVMSharedErrorNum(e) => format!("syscall failed: {}", e),
+            Error::FVMSDKNoState(e) => e.to_string(),
+            Error::FVMEncoding(e) => format!("encoding: {}", e),
+            Error::FVMSharedAddress(e) => format!("address: {}", e),
+            Error::UnresolvedAddress(a) => format!("address {} does not resolve to an actor", a),
+            Error::NotOwner => String::from("caller is not the owner"),
+            Error::NotExists => String::from("not found"),
+            Error::NotCaller => String::from("caller not allowed"),
+            Error::InvalidQuorum => String::from("quorum must be a fraction below one"),
+            Error::InvalidPeerID(p) => format!("invalid peer id {:?}", p),
+            Error::InvalidMultiAddr(a) => format!("invalid multiaddr {:?}", a),
+            Error::TooManyAddresses => format!("at most {} addresses per node", MAX_ADDRESSES),
+            Error::InvalidHealthCheck(r) => format!("invalid health check: {}", r),
+            Error::NotAssigned => String::from("checker not assigned to the member"),
+            Error::FutureEpoch => String::from("epoch is in the future"),
+            Error::InvalidParams(r) => format!("invalid params: {}", r),
+            Error::WrongVotingPhase => String::from("not accepted in the current voting phase or mode"),
+            Error::InvalidCommitment => String::from("reveal does not match the commitment"),
+            Error::EvictionPending => String::from("checker eviction pending"),
+            Error::Jailed => String::from("checker is jailed"),
+            Error::AlreadyExists => String::from("already exists"),
+            Error::InsufficientDeposit => String::from("value below the member deposit"),
+            Error::MemberCapReached => String::from("maximum number of members reached"),
+            Error::CreatorCapReached(c) => format!("creator {} reached its maximum number of members", c),
+            Error::RefundFailed => String::from("deposit refund failed"),
+            Error::TooManySubscribers => String::from("maximum number of subscribers reached"),
+            Error::StateVersion(v) => format!("unsupported state version {}, see migrate", v),
+            Error::BatchFailed(errors) => {
+                let items: Vec<String> = errors.iter().map(|(i, e)| format!("#{} {}", i, e)).collect();
+                format!("{} items failed: {}", errors.len(), items.join("; "))
+            }
         }
     }
 }
 
+/// The abort message, the name of the error followed by its details
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name(), self.msg())
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl From<fvm_ipld_hamt::Error> for Error {
     fn from(e: fvm_ipld_hamt::Error) -> Self {
         Error::FVMIpldHamt(e)
@@ -76,3 +211,119 @@ impl From<fvm_shared::address::Error> for Error {
         Error::FVMSharedAddress(e)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fvm_shared::error::ErrorNumber;
+    use uptime_checker_interface::errors::{lookup, CATALOGUE};
+
+    /// An instance of every variant, listed through a match so that a new
+    /// variant fails to compile until it is added here.
+    fn every_variant() -> Vec<Error> {
+        let all = vec![
+            Error::AlreadyVoted(PeerID::from("peer")),
+            Error::CannotDeserialize,
+            Error::FVMIpldHamt(fvm_ipld_hamt::Error::MaxDepth),
+            Error::Anyhow(anyhow::anyhow!("failed")),
+            Error::FVMSharedErrorNum(ErrorNumber::NotFound),
+            Error::FVMSharedErrorNum(ErrorNumber::Forbidden),
+            Error::FVMSharedErrorNum(ErrorNumber::IllegalArgument),
+            Error::FVMSDKNoState(fvm_sdk::error::NoStateError),
+            Error::FVMEncoding(fvm_ipld_encoding::from_slice::<u64>(&[0xff]).unwrap_err()),
+            Error::FVMSharedAddress(fvm_shared::address::Error::NonIDAddress),
+            Error::FVMSharedAddress(fvm_shared::address::Error::UnknownNetwork),
+            Error::UnresolvedAddress(Address::new_id(100)),
+            Error::NotOwner,
+            Error::NotExists,
+            Error::NotCaller,
+            Error::InvalidQuorum,
+            Error::InvalidPeerID(PeerID::from("peer")),
+            Error::InvalidMultiAddr(MultiAddr::from("addr")),
+            Error::TooManyAddresses,
+            Error::InvalidHealthCheck(String::from("check")),
+            Error::NotAssigned,
+            Error::FutureEpoch,
+            Error::InvalidParams(String::from("params")),
+            Error::WrongVotingPhase,
+            Error::InvalidCommitment,
+            Error::EvictionPending,
+            Error::Jailed,
+            Error::AlreadyExists,
+            Error::InsufficientDeposit,
+            Error::MemberCapReached,
+            Error::CreatorCapReached(100),
+            Error::RefundFailed,
+            Error::TooManySubscribers,
+            Error::StateVersion(1),
+            Error::BatchFailed(vec![(0, Error::NotExists)]),
+        ];
+        for e in all.iter() {
+            match e {
+                Error::AlreadyVoted(_)
+                | Error::CannotDeserialize
+                | Error::FVMIpldHamt(_)
+                | Error::Anyhow(_)
+                | Error::FVMSharedErrorNum(_)
+                | Error::FVMSDKNoState(_)
+                | Error::FVMEncoding(_)
+                | Error::FVMSharedAddress(_)
+                | Error::UnresolvedAddress(_)
+                | Error::NotOwner
+                | Error::NotExists
+                | Error::NotCaller
+                | Error::InvalidQuorum
+                | Error::InvalidPeerID(_)
+                | Error::InvalidMultiAddr(_)
+                | Error::TooManyAddresses
+                | Error::InvalidHealthCheck(_)
+                | Error::NotAssigned
+                | Error::FutureEpoch
+                | Error::InvalidParams(_)
+                | Error::WrongVotingPhase
+                | Error::InvalidCommitment
+                | Error::EvictionPending
+                | Error::Jailed
+                | Error::AlreadyExists
+                | Error::InsufficientDeposit
+                | Error::MemberCapReached
+                | Error::CreatorCapReached(_)
+                | Error::RefundFailed
+                | Error::TooManySubscribers
+                | Error::StateVersion(_)
+                | Error::BatchFailed(_) => {}
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn every_error_matches_its_catalogue_entry() {
+        for e in every_variant() {
+            let entry = lookup(e.name()).unwrap_or_else(|| panic!("{} is not in the catalogue", e.name()));
+            assert_eq!(entry.exit_code, e.code(), "exit code of {}", e);
+        }
+    }
+
+    #[test]
+    fn catalogue_lists_every_error_once() {
+        let mut names: Vec<&str> = every_variant().iter().map(|e| e.name()).collect();
+        names.sort_unstable();
+        names.dedup();
+        let mut catalogue: Vec<&str> = CATALOGUE.iter().map(|e| e.name).collect();
+        catalogue.sort_unstable();
+        assert_eq!(names, catalogue);
+    }
+
+    #[test]
+    fn abort_messages_parse_back() {
+        use uptime_checker_interface::errors::parse_abort_message;
+
+        for e in every_variant() {
+            let msg = e.to_string();
+            let (entry, details) = parse_abort_message(&msg).unwrap();
+            assert_eq!(entry.name, e.name());
+            assert_eq!(details, e.msg());
+        }
+    }
+}
@@ -1,181 +1,996 @@
-use crate::blockstore::{make_empty_map, Blockstore, get_map_from_cid};
-use crate::types::{NodeInfo, Votes};
+use crate::blockstore::{make_empty_map, u64_key, Blockstore, get_map_from_cid, LazyMap, Map};
+use crate::migration::{nodes_v0, rekey_v1, StateV0, STATE_VERSION};
+use crate::types::{
+    CommitRound, InitParams, NamespaceId, DEFAULT_CHECKERS_PER_MEMBER, DEFAULT_HAMT_BIT_WIDTH, DEFAULT_NAMESPACE, NodeInfo, NodeKind, PendingEviction, Quorum, StatusChange,
+    StatusNotification, Subscription, Votes, VotingMode,
+};
+use crate::runtime;
 use crate::Error;
 use cid::Cid;
 use fvm_ipld_encoding::{to_vec, CborStore, DAG_CBOR};
-use fvm_ipld_hamt::BytesKey;
-use fvm_shared::ActorID;
+use fvm_ipld_hamt::{BytesKey, Error as HamtError};
+use fvm_shared::{ActorID, MethodNum};
 use fvm_shared::clock::ChainEpoch;
+use fvm_shared::bigint::bigint_ser;
+use fvm_shared::econ::TokenAmount;
+use crate::types::PeerID;
 use multihash::Code;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use crate::traits::LoadableState;
 
 const DEFAULT_VOTING_DURATION: ChainEpoch = 200;
+const DEFAULT_ASSIGNMENT_WINDOW: ChainEpoch = 120;
+const DEFAULT_APPEAL_WINDOW: ChainEpoch = 120;
+const DEFAULT_JAIL_COOLDOWN: ChainEpoch = 2880;
+/// Caps the subscribers registered by the admins
+const MAX_SUBSCRIBERS: u64 = 16;
 
-/// The state object.
+/// A HAMT of nodes keyed by PeerID
+type NodeMap = Map<'static, Blockstore, NodeInfo>;
+
+/// The root state object.
 #[derive(Debug, Serialize, Deserialize)]
+pub struct RootState {
+    /// The version of the state layout, see `STATE_VERSION`
+    pub(crate) version: u64,
+    /// The independent registries of the actor, with the default bit width
+    pub(crate) namespaces: Cid, // HAMT<BytesKey from NamespaceId, HamtState>
+    /// The actors managing the namespaces and subscribers, the creators
+    /// of the checkers the actor was constructed with
+    #[serde(default)]
+    pub(crate) admins: Vec<ActorID>,
+}
+
+/// The state of a single namespace, i.e. an independent registry with
+/// its own checkers, members and voting parameters.
+///
+/// Its HAMTs are loaded on first use and only flushed by `save`, the CID
+/// fields are their roots as of the last load, superseded by the roots
+/// their handles were last flushed to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HamtState {
+    /// The namespace the state is stored under, set when loaded
+    #[serde(skip)]
+    pub(crate) namespace: NamespaceId,
     /// The list of node members in the registry
-    members: Cid, // HAMT<BytesKey from ActorID, NodeInfo>
+    pub(crate) members: Cid, // HAMT<BytesKey from PeerID, NodeInfo>
     /// List of checkers registered in the system.
-    checkers: Cid, // HAMT<BytesKey from ActorID, NodeInfo>
+    pub(crate) checkers: Cid, // HAMT<BytesKey from PeerID, NodeInfo>
     /// Data structure used to signal offline checkers.
-    offline_checkers: Cid, // HAMT<BytesKey, Votes>
-    /// The total number of checkers
-    total_checkers: usize,
+    pub(crate) offline_checkers: Cid, // HAMT<BytesKey from PeerID, Votes>
+    /// Data structure used to signal offline members.
+    pub(crate) offline_members: Cid, // HAMT<BytesKey from PeerID, Votes>
+    /// The total number of active checkers
+    pub(crate) total_checkers: usize,
     /// The voting duration threshold
-    voting_duration: ChainEpoch,
+    pub(crate) voting_duration: ChainEpoch,
+    /// Whether offline checkers are voted instantly or by commit-reveal
+    pub(crate) voting_mode: VotingMode,
+    /// Open commit-reveal rounds on offline checkers.
+    pub(crate) vote_commits: Cid, // HAMT<BytesKey from PeerID, CommitRound>
+    /// Epochs an offline checker has to appeal, 0 evicts right away
+    pub(crate) appeal_window: ChainEpoch,
+    /// Checkers voted offline, waiting for the end of their appeal window.
+    pub(crate) pending_evictions: Cid, // HAMT<BytesKey from PeerID, PendingEviction>
+    /// Epochs an evicted checker stays jailed on its first strike
+    pub(crate) jail_cooldown: ChainEpoch,
+    /// The fraction of checkers needed to remove an offline checker
+    pub(crate) quorum: Quorum,
+    /// The number of checkers assigned to each member
+    pub(crate) checkers_per_member: u64,
+    /// The length of the checker assignment windows
+    pub(crate) assignment_window: ChainEpoch,
+    /// Refundable deposit required to register a member
+    #[serde(with = "bigint_ser")]
+    pub(crate) member_deposit: TokenAmount,
+    /// The total number of members
+    pub(crate) total_members: u64,
+    /// Cap on the total number of members, if any
+    pub(crate) max_members: Option<u64>,
+    /// Cap on the number of members per creator, if any
+    pub(crate) max_members_per_creator: Option<u64>,
+    /// Number of members registered by each creator.
+    pub(crate) member_counts: Cid, // HAMT<varint ActorID, u64>
+    /// Actors notified of status changes.
+    pub(crate) subscribers: Cid, // HAMT<varint ActorID, Subscription>
+    /// The total number of subscribers
+    pub(crate) total_subscribers: u64,
+    /// The sequence number of the next notification
+    #[serde(default)]
+    pub(crate) next_notification: u64,
+    /// The bit width of every HAMT of the namespace, version 1 states
+    /// did not store it and used the default
+    #[serde(default = "default_hamt_bit_width")]
+    pub(crate) hamt_bit_width: u32,
+    /// The HAMTs used in the current invocation
+    #[serde(skip)]
+    handles: Handles,
+}
+
+/// The loaded HAMTs of a `HamtState`, one per CID field
+#[derive(Debug, Default, Clone)]
+struct Handles {
+    members: LazyMap<NodeInfo>,
+    checkers: LazyMap<NodeInfo>,
+    offline_checkers: LazyMap<Votes>,
+    offline_members: LazyMap<Votes>,
+    vote_commits: LazyMap<CommitRound>,
+    pending_evictions: LazyMap<PendingEviction>,
+    member_counts: LazyMap<u64>,
+    subscribers: LazyMap<Subscription>,
+}
+
+impl Handles {
+    fn new(bit_width: u32) -> Self {
+        Handles {
+            members: LazyMap::new(bit_width),
+            checkers: LazyMap::new(bit_width),
+            offline_checkers: LazyMap::new(bit_width),
+            offline_members: LazyMap::new(bit_width),
+            vote_commits: LazyMap::new(bit_width),
+            pending_evictions: LazyMap::new(bit_width),
+            member_counts: LazyMap::new(bit_width),
+            subscribers: LazyMap::new(bit_width),
+        }
+    }
+}
+
+/// States are equal when they store the same, whether their HAMTs are
+/// loaded or not. The changed HAMTs are flushed to compare their roots.
+impl PartialEq for HamtState {
+    fn eq(&self, other: &Self) -> bool {
+        let stored = |s: &Self| s.flushed().and_then(|f| Ok(to_vec(&f)?));
+        self.namespace == other.namespace && matches!((stored(self), stored(other)), (Ok(a), Ok(b)) if a == b)
+    }
+}
+
+/// Stops a HAMT walk, `for_each` has no other way to break early
+#[derive(Debug)]
+struct StopWalk;
+
+impl fmt::Display for StopWalk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("walk stopped")
+    }
+}
+
+impl std::error::Error for StopWalk {}
+
+fn default_hamt_bit_width() -> u32 {
+    DEFAULT_HAMT_BIT_WIDTH
 }
 
 impl HamtState {
+    fn load_root() -> Result<RootState, Error> {
+        let root = runtime::root()?;
+        let root = (Blockstore.get_cbor::<RootState>(&root)?).ok_or(Error::CannotDeserialize)?;
+        if root.version != STATE_VERSION {
+            return Err(Error::StateVersion(root.version));
+        }
+        Ok(root)
+    }
+
+    /// Converts a version 0 state into the `DEFAULT_NAMESPACE` registry.
+    /// The open offline votes were cast by ActorID and are dropped.
+    fn from_v0(v0: StateV0) -> Result<Self, Error> {
+        let params = InitParams {
+            voting_duration: Some(v0.voting_duration),
+            ..Default::default()
+        };
+        let epoch = runtime::curr_epoch();
+        let checkers = nodes_v0(&Blockstore, &v0.checkers, epoch)?;
+        let mut s = Self::new(&NamespaceId::from(DEFAULT_NAMESPACE), checkers, &params)?;
+
+        for n in nodes_v0(&Blockstore, &v0.members, epoch)? {
+            s.add_member_count(n.creator(), 1)?;
+            s.total_members += 1;
+            s.handles.members.with_mut(&s.members, |m| {
+                m.set(Self::key(n.id()), n)?;
+                Ok(())
+            })?;
+        }
+        Ok(s)
+    }
+
+    /// Re-keys the ActorID maps of every namespace of a version 1 state,
+    /// the other maps are unchanged. The admins are kept, states stored
+    /// before there were admins get the default namespace checker creators.
+    fn migrate_v1(root: RootState) -> Result<(), Error> {
+        let mut map = get_map_from_cid::<_, HamtState>(&root.namespaces, &Blockstore, DEFAULT_HAMT_BIT_WIDTH)?;
+        let admins = if root.admins.is_empty() {
+            map.get(&BytesKey::from(DEFAULT_NAMESPACE))?.ok_or(Error::NotExists)?.checker_creators()?
+        } else {
+            root.admins
+        };
+        Self::ensure_migrator(&admins)?;
+
+        let mut states = vec![];
+        map.for_each(|k, s| {
+            states.push((k.clone(), s.clone()));
+            Ok(())
+        })?;
+        for (k, mut s) in states {
+            s.member_counts = rekey_v1::<_, u64>(&Blockstore, &s.member_counts)?;
+            s.subscribers = rekey_v1::<_, Subscription>(&Blockstore, &s.subscribers)?;
+            map.set(k, s)?;
+        }
+        Self::save_root(&RootState { version: STATE_VERSION, namespaces: map.flush()?, admins })?;
+        Ok(())
+    }
+
+    fn save_root(root: &RootState) -> Result<Cid, Error> {
+        let serialized = to_vec(root)?;
+        let cid = runtime::put_block(Code::Blake2b256.into(), DAG_CBOR, serialized.as_slice())?;
+        runtime::set_root(&cid)?;
+        Ok(cid)
+    }
+
+    /// The distinct creators of the checkers, the admins of the states
+    /// stored before the admins were
+    fn checker_creators(&self) -> Result<Vec<ActorID>, Error> {
+        self.handles.checkers.with(&self.checkers, |map| {
+            let mut creators = vec![];
+            map.for_each(|_, n| {
+                if !creators.contains(n.creator()) {
+                    creators.push(*n.creator());
+                }
+                Ok(())
+            })?;
+            Ok(creators)
+        })
+    }
+
+    /// Only the admins of a migrated state may migrate it, so that the
+    /// upgrade happens when the deployment is ready for it.
+    fn ensure_migrator(admins: &[ActorID]) -> Result<(), Error> {
+        if admins.contains(&runtime::caller()) {
+            Ok(())
+        } else {
+            Err(Error::NotCaller)
+        }
+    }
+
     fn ensure_owner(b: &NodeInfo) -> Result<(), Error> {
-        if fvm_sdk::message::caller() != *b.creator() {
+        if runtime::caller() != *b.creator() {
             Err(Error::NotOwner)
         } else {
             Ok(())
         }
     }
 
-    fn upsert(store: &Blockstore, map_cid: &Cid, node: NodeInfo) -> Result<Cid, Error> {
-        let mut map = get_map_from_cid(map_cid, store)?;
-
-        let str = &node.creator().to_string()[..];
-        let id = BytesKey::from(str);
-        let n = map.get(&id)?.unwrap_or(&node);
+    fn key(id: &PeerID) -> BytesKey {
+        BytesKey::from(id.as_str())
+    }
 
-        Self::ensure_owner(n)?;
+    /// Inserts or updates the node keyed by its PeerID. Returns whether
+    /// the node was newly inserted.
+    fn upsert(map: &mut NodeMap, node: NodeInfo) -> Result<bool, Error> {
+        let id = Self::key(node.id());
+        let (node, inserted) = match map.get(&id)? {
+            Some(n) => {
+                Self::ensure_owner(n)?;
+                let mut n = n.clone();
+                n.apply_edit(node, runtime::curr_epoch());
+                (n, false)
+            }
+            None => {
+                Self::ensure_owner(&node)?;
+                (node, true)
+            }
+        };
 
         map.set(id, node)?;
-        Ok(map.flush()?)
+        Ok(inserted)
     }
 
-    fn remove(store: &Blockstore, map_cid: &Cid, id: &ActorID) -> Result<Cid, Error> {
-        let mut map = get_map_from_cid(map_cid, store)?;
+    /// The nodes of `kind` and the root they are loaded from
+    fn nodes(&self, kind: NodeKind) -> (&LazyMap<NodeInfo>, &Cid) {
+        match kind {
+            NodeKind::Checker => (&self.handles.checkers, &self.checkers),
+            NodeKind::Member => (&self.handles.members, &self.members),
+        }
+    }
 
-        let key = BytesKey::from(&id.to_string()[..]);
+    /// The offline votes on the nodes of `kind` and the root they are
+    /// loaded from
+    fn offline(&self, kind: NodeKind) -> (&LazyMap<Votes>, &Cid) {
+        match kind {
+            NodeKind::Checker => (&self.handles.offline_checkers, &self.offline_checkers),
+            NodeKind::Member => (&self.handles.offline_members, &self.offline_members),
+        }
+    }
+
+
+    /// Applies `f` to the node keyed by `id` and stores the result.
+    fn update<F>(&self, kind: NodeKind, id: &PeerID, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut NodeInfo) -> Result<(), Error>,
+    {
+        let (nodes, cid) = self.nodes(kind);
+        nodes.with_mut(cid, |map| {
+            let key = Self::key(id);
+            let mut n = map.get(&key)?.ok_or(Error::NotExists)?.clone();
+            f(&mut n)?;
+
+            map.set(key, n)?;
+            Ok(())
+        })
+    }
+
+    /// Adds `delta` to the number of members owned by `creator`, failing
+    /// if the per creator cap is exceeded.
+    fn add_member_count(&mut self, creator: &ActorID, delta: i64) -> Result<(), Error> {
+        let max = self.max_members_per_creator;
+        self.handles.member_counts.with_mut(&self.member_counts, |map| {
+            let key = u64_key(*creator);
+
+            let count = (*map.get(&key)?.unwrap_or(&0) as i64 + delta) as u64;
+            if delta > 0 && max.map(|m| count > m).unwrap_or(false) {
+                return Err(Error::CreatorCapReached(*creator));
+            }
+
+            if count == 0 {
+                map.delete(&key)?;
+            } else {
+                map.set(key, count)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn remove(map: &mut NodeMap, id: &PeerID) -> Result<NodeInfo, Error> {
+        let key = Self::key(id);
         let n = map.get(&key)?.ok_or(Error::NotExists)?;
 
         Self::ensure_owner(n)?;
+        // jailed nodes must serve their time, otherwise they
+        // could re-register right away with a clean history
+        if !n.is_active() {
+            return Err(Error::Jailed);
+        }
 
-        map.delete(&key)?;
-        Ok(map.flush()?)
+        let (_, n) = map.delete(&key)?.ok_or(Error::NotExists)?;
+        Ok(n)
+    }
+
+    /// The state with its changed HAMTs flushed, as it is stored
+    fn flushed(&self) -> Result<Self, Error> {
+        let h = &self.handles;
+        Ok(HamtState {
+            members: h.members.flush(&self.members)?,
+            checkers: h.checkers.flush(&self.checkers)?,
+            offline_checkers: h.offline_checkers.flush(&self.offline_checkers)?,
+            offline_members: h.offline_members.flush(&self.offline_members)?,
+            vote_commits: h.vote_commits.flush(&self.vote_commits)?,
+            pending_evictions: h.pending_evictions.flush(&self.pending_evictions)?,
+            member_counts: h.member_counts.flush(&self.member_counts)?,
+            subscribers: h.subscribers.flush(&self.subscribers)?,
+            ..self.clone()
+        })
     }
 }
 
 impl LoadableState for HamtState {
-    fn new(nodes: Vec<NodeInfo>, voting_duration: &Option<ChainEpoch>) -> Result<Self, Error> {
+    fn init_root(admins: Vec<ActorID>) -> Result<(), Error> {
+        Self::save_root(&RootState {
+            version: STATE_VERSION,
+            namespaces: make_empty_map::<_, HamtState>(&Blockstore, DEFAULT_HAMT_BIT_WIDTH).flush()?,
+            admins,
+        })?;
+        Ok(())
+    }
+
+    fn is_admin(actor: &ActorID) -> Result<bool, Error> {
+        Ok(Self::load_root()?.admins.contains(actor))
+    }
+
+    fn has_namespace(namespace: &NamespaceId) -> Result<bool, Error> {
+        let root = Self::load_root()?;
+        let map = get_map_from_cid::<_, HamtState>(&root.namespaces, &Blockstore{}, DEFAULT_HAMT_BIT_WIDTH)?;
+        Ok(map.contains_key(&BytesKey::from(namespace.as_str()))?)
+    }
+
+    fn new(namespace: &NamespaceId, nodes: Vec<NodeInfo>, params: &InitParams) -> Result<Self, Error> {
+        let quorum = match &params.quorum {
+            Some(q) => Quorum::new(q.numerator, q.denominator)?,
+            None => Quorum::default(),
+        };
+        let checkers_per_member = params.checkers_per_member.unwrap_or(DEFAULT_CHECKERS_PER_MEMBER);
+        if checkers_per_member == 0 {
+            return Err(Error::InvalidParams(String::from("checkers_per_member must be positive")));
+        }
+        let member_deposit = params.member_deposit()?;
+        let bit_width = params.hamt_bit_width()?;
+        let assignment_window = params.assignment_window.unwrap_or(DEFAULT_ASSIGNMENT_WINDOW);
+        if assignment_window <= 0 {
+            return Err(Error::InvalidParams(String::from("assignment_window must be positive")));
+        }
+        let jail_cooldown = params.jail_cooldown.unwrap_or(DEFAULT_JAIL_COOLDOWN);
+        if jail_cooldown < 0 {
+            return Err(Error::InvalidParams(String::from("jail_cooldown must not be negative")));
+        }
+        let appeal_window = params.appeal_window.unwrap_or(DEFAULT_APPEAL_WINDOW);
+        if appeal_window < 0 {
+            return Err(Error::InvalidParams(String::from("appeal_window must not be negative")));
+        }
+        if let VotingMode::CommitReveal { commit_duration, reveal_duration } = params.voting_mode {
+            if commit_duration <= 0 || reveal_duration <= 0 {
+                return Err(Error::InvalidParams(String::from("commit and reveal durations must be positive")));
+            }
+        }
         let total_checkers = nodes.len();
-        let mut checker_map = make_empty_map::<_, NodeInfo>(&Blockstore);
+        let mut checker_map = make_empty_map::<_, NodeInfo>(&Blockstore, bit_width);
         for n in nodes {
-            let str = &n.creator().to_string()[..];
-            checker_map.set(BytesKey::from(str), n)?;
+            checker_map.set(Self::key(n.id()), n)?;
         }
         Ok(HamtState {
-            members: make_empty_map::<_, NodeInfo>(&Blockstore).flush()?,
+            namespace: namespace.clone(),
+            members: make_empty_map::<_, NodeInfo>(&Blockstore, bit_width).flush()?,
             checkers: checker_map.flush()?,
-            offline_checkers: make_empty_map::<_, Votes>(&Blockstore).flush()?,
+            offline_checkers: make_empty_map::<_, Votes>(&Blockstore, bit_width).flush()?,
+            offline_members: make_empty_map::<_, Votes>(&Blockstore, bit_width).flush()?,
             total_checkers,
-            voting_duration: voting_duration.unwrap_or(DEFAULT_VOTING_DURATION)
+            voting_duration: params.voting_duration.unwrap_or(DEFAULT_VOTING_DURATION),
+            voting_mode: params.voting_mode,
+            vote_commits: make_empty_map::<_, CommitRound>(&Blockstore, bit_width).flush()?,
+            appeal_window,
+            pending_evictions: make_empty_map::<_, PendingEviction>(&Blockstore, bit_width).flush()?,
+            jail_cooldown,
+            quorum,
+            checkers_per_member,
+            assignment_window,
+            member_deposit,
+            total_members: 0,
+            max_members: params.max_members,
+            max_members_per_creator: params.max_members_per_creator,
+            member_counts: make_empty_map::<_, u64>(&Blockstore, bit_width).flush()?,
+            subscribers: make_empty_map::<_, Subscription>(&Blockstore, bit_width).flush()?,
+            total_subscribers: 0,
+            next_notification: 0,
+            hamt_bit_width: bit_width,
+            handles: Handles::new(bit_width),
         })
     }
 
     fn upsert_node(&mut self, node: NodeInfo) -> Result<(), Error> {
-        self.members = Self::upsert(&Blockstore{}, &self.members, node)?;
+        let creator = *node.creator();
+        let inserted = self.handles.members.with_mut(&self.members, |map| Self::upsert(map, node))?;
+        if inserted {
+            self.total_members += 1;
+            if self.max_members.map(|m| self.total_members > m).unwrap_or(false) {
+                return Err(Error::MemberCapReached);
+            }
+            self.add_member_count(&creator, 1)?;
+        }
         Ok(())
     }
 
-    fn remove_node(&mut self, id: &ActorID) -> Result<(), Error> {
-        self.members = Self::remove(&Blockstore{}, &self.members, id)?;
-        Ok(())
+    fn remove_node(&mut self, id: &PeerID) -> Result<NodeInfo, Error> {
+        let node = self.handles.members.with_mut(&self.members, |map| Self::remove(map, id))?;
+        self.total_members -= 1;
+        self.add_member_count(node.creator(), -1)?;
+        Ok(node)
+    }
+
+    fn node(&self, kind: NodeKind, id: &PeerID) -> Result<Option<NodeInfo>, Error> {
+        let (nodes, cid) = self.nodes(kind);
+        nodes.with(cid, |map| Ok(map.get(&Self::key(id))?.cloned()))
+    }
+
+    fn is_member(&self, member: &PeerID) -> Result<bool, Error> {
+        self.handles.members.with(&self.members, |map| Ok(map.contains_key(&Self::key(member))?))
+    }
+
+    fn set_member_offline(&mut self, member: &PeerID, epoch: ChainEpoch) -> Result<(), Error> {
+        self.update(NodeKind::Member, member, |n| {
+            n.set_offline(epoch);
+            Ok(())
+        })?;
+
+        // the verdict is reached, start a new round
+        self.clear_votes(NodeKind::Member, member)
+    }
+
+    fn for_each_id<F>(&self, kind: NodeKind, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&PeerID) -> bool,
+    {
+        let (nodes, cid) = self.nodes(kind);
+        nodes.with(cid, |map| {
+            let walk = map.for_each(|_, n| {
+                if n.is_active() && !f(n.id()) {
+                    return Err(StopWalk.into());
+                }
+                Ok(())
+            });
+            match walk {
+                Err(HamtError::Dynamic(e)) if e.is::<StopWalk>() => Ok(()),
+                r => Ok(r?),
+            }
+        })
+    }
+
+    fn checker_ids(&self) -> Result<Vec<PeerID>, Error> {
+        let mut ids = vec![];
+        self.for_each_id(NodeKind::Checker, |id| {
+            ids.push(id.clone());
+            true
+        })?;
+        Ok(ids)
     }
 
-    fn is_checker(&self, checker: &ActorID) -> Result<bool, Error> {
-        let map = get_map_from_cid::<_, NodeInfo>(&self.checkers, &Blockstore{})?;
-        let key = BytesKey::from(&checker.to_string()[..]);
-        Ok(map.contains_key(&key)?)
+    fn is_checker(&self, checker: &PeerID, owner: &ActorID) -> Result<bool, Error> {
+        self.handles.checkers.with(&self.checkers, |map| {
+            Ok(
+                map.get(&Self::key(checker))?
+                    .map(|n| n.creator() == owner && n.is_active())
+                    .unwrap_or(false)
+            )
+        })
     }
 
     fn upsert_checker(&mut self, node: NodeInfo) -> Result<(), Error> {
-        self.checkers = Self::upsert(&Blockstore{}, &self.checkers, node)?;
+        let inserted = self.handles.checkers.with_mut(&self.checkers, |map| Self::upsert(map, node))?;
+        if inserted {
+            self.total_checkers += 1;
+        }
         Ok(())
     }
 
-    fn remove_checker(&mut self, id: &ActorID) -> Result<(), Error> {
-        self.checkers = Self::remove(&Blockstore{}, &self.checkers, id)?;
+    fn remove_checker(&mut self, id: &PeerID) -> Result<(), Error> {
+        // like jailed ones, checkers voted offline must not escape
+        // their verdict by leaving and registering again
+        if self.pending_eviction(id)?.is_some() {
+            return Err(Error::EvictionPending);
+        }
+        self.handles.checkers.with_mut(&self.checkers, |map| Self::remove(map, id))?;
+        self.total_checkers -= 1;
         Ok(())
     }
 
-    fn remove_checker_unchecked(&mut self, checker: &ActorID) -> Result<(), Error> {
-        let mut map = get_map_from_cid::<_, NodeInfo>(&self.checkers, &Blockstore{})?;
-        map.delete(&BytesKey::from(&checker.to_string()[..]))?;
-        self.checkers = map.flush()?;
-        Ok(())
+    fn jail_checker(&mut self, checker: &PeerID) -> Result<bool, Error> {
+        let epoch = runtime::curr_epoch();
+        let cooldown = self.jail_cooldown;
+        let jailed = self.handles.checkers.with_mut(&self.checkers, |map| {
+            let key = Self::key(checker);
+
+            // the checker may have left in the meantime
+            let mut n = match map.get(&key)? {
+                Some(n) if n.is_active() => n.clone(),
+                _ => return Ok(false),
+            };
+            n.jail(epoch, cooldown);
+
+            map.set(key, n)?;
+            Ok(true)
+        })?;
+        if jailed {
+            self.total_checkers -= 1;
+        }
+        Ok(jailed)
     }
 
-    fn has_voted(&self, reported: &ActorID, voter: &ActorID) -> Result<bool, Error> {
-        let map = get_map_from_cid::<_, Votes>(&self.offline_checkers, &Blockstore{})?;
-        Ok(
-            map.get(&BytesKey::from(&reported.to_string()[..]))?
-                .map(|v| v.has_voted(voter))
-                .unwrap_or(false)
-        )
+    fn unjail_checker(&mut self, checker: &PeerID) -> Result<(), Error> {
+        let epoch = runtime::curr_epoch();
+        self.update(NodeKind::Checker, checker, |n| {
+            Self::ensure_owner(n)?;
+            n.unjail(epoch)
+        })?;
+        self.total_checkers += 1;
+        Ok(())
     }
 
-    fn record_voted(&mut self, reported: &ActorID, voter: &ActorID) -> Result<usize, Error> {
-        let mut map = get_map_from_cid::<_, Votes>(&self.offline_checkers, &Blockstore{})?;
-        let reported_key = BytesKey::from(&reported.to_string()[..]);
+    fn transfer_ownership(
+        &mut self,
+        kind: NodeKind,
+        id: &PeerID,
+        new_owner: ActorID,
+        require_accept: bool,
+    ) -> Result<(), Error> {
+        let mut previous = None;
+        self.update(kind, id, |n| {
+            Self::ensure_owner(n)?;
+            if require_accept {
+                n.set_pending_owner(new_owner);
+            } else {
+                previous = Some(*n.creator());
+                n.set_owner(new_owner);
+            }
+            Ok(())
+        })?;
+        if let (NodeKind::Member, Some(previous)) = (kind, previous) {
+            self.add_member_count(&previous, -1)?;
+            self.add_member_count(&new_owner, 1)?;
+        }
+        Ok(())
+    }
 
-        match map.get(&reported_key)? {
-            None => {
-                let mut votes = Votes::new(fvm_sdk::network::curr_epoch());
-                votes.vote(voter);
-                map.set(reported_key, votes)?;
-                self.offline_checkers = map.flush()?;
-                Ok(1)
+    fn accept_ownership(&mut self, kind: NodeKind, id: &PeerID) -> Result<(), Error> {
+        let caller = runtime::caller();
+        let mut previous = caller;
+        self.update(kind, id, |n| {
+            if *n.pending_owner() != Some(caller) {
+                return Err(Error::NotOwner);
             }
-            Some(votes) => {
-                let t = self.vote_duration_threshold();
-                if !votes.within_threshold(fvm_sdk::network::curr_epoch(), t) {
-                    // delete the current round and start again
-                    let mut votes = Votes::new(fvm_sdk::network::curr_epoch());
+            previous = *n.creator();
+            n.set_owner(caller);
+            Ok(())
+        })?;
+        if kind == NodeKind::Member {
+            self.add_member_count(&previous, -1)?;
+            self.add_member_count(&caller, 1)?;
+        }
+        Ok(())
+    }
+
+    fn has_voted(&self, kind: NodeKind, reported: &PeerID, voter: &PeerID) -> Result<bool, Error> {
+        let (offline, cid) = self.offline(kind);
+        offline.with(cid, |map| {
+            Ok(
+                map.get(&Self::key(reported))?
+                    .map(|v| v.has_voted(voter))
+                    .unwrap_or(false)
+            )
+        })
+    }
+
+    fn record_voted(&mut self, kind: NodeKind, reported: &PeerID, voter: &PeerID) -> Result<usize, Error> {
+        let t = self.vote_duration_threshold();
+        let epoch = runtime::curr_epoch();
+        let (offline, cid) = self.offline(kind);
+        offline.with_mut(cid, |map| {
+            let reported_key = Self::key(reported);
+
+            match map.get(&reported_key)? {
+                None => {
+                    let mut votes = Votes::new(epoch);
                     votes.vote(voter);
+                    map.set(reported_key, votes)?;
+                    Ok(1)
+                }
+                Some(votes) => {
+                    if !votes.within_threshold(epoch, t) {
+                        // delete the current round and start again
+                        let mut votes = Votes::new(epoch);
+                        votes.vote(voter);
+
+                        map.set(reported_key, votes)?;
+                        return Ok(1);
+                    }
+
+                    if votes.has_voted(voter) {
+                        return Err(Error::AlreadyVoted(voter.clone()));
+                    }
+
+                    // TODO: make votes HAMT as well
+                    let mut votes = votes.clone();
+                    votes.vote(voter);
+                    let total = votes.total_votes();
 
                     map.set(reported_key, votes)?;
+                    Ok(total)
+                }
+            }
+        })
+    }
 
-                    self.offline_checkers = map.flush()?;
-                    return Ok(0);
+    fn commit_round(&self, reported: &PeerID) -> Result<Option<CommitRound>, Error> {
+        self.handles.vote_commits.with(&self.vote_commits, |map| Ok(map.get(&Self::key(reported))?.cloned()))
+    }
+
+    fn set_commit_round(&mut self, reported: &PeerID, round: Option<CommitRound>) -> Result<(), Error> {
+        self.handles.vote_commits.with_mut(&self.vote_commits, |map| {
+            match round {
+                Some(r) => {
+                    map.set(Self::key(reported), r)?;
+                }
+                None => {
+                    map.delete(&Self::key(reported))?;
                 }
+            }
+            Ok(())
+        })
+    }
+
+    fn pending_eviction(&self, checker: &PeerID) -> Result<Option<PendingEviction>, Error> {
+        self.handles.pending_evictions.with(&self.pending_evictions, |map| Ok(map.get(&Self::key(checker))?.cloned()))
+    }
 
-                if votes.has_voted(voter) {
-                    return Err(Error::AlreadyVoted(*voter));
+    fn set_pending_eviction(&mut self, checker: &PeerID, pending: Option<PendingEviction>) -> Result<(), Error> {
+        self.handles.pending_evictions.with_mut(&self.pending_evictions, |map| {
+            match pending {
+                Some(p) => {
+                    map.set(Self::key(checker), p)?;
+                }
+                None => {
+                    map.delete(&Self::key(checker))?;
                 }
+            }
+            Ok(())
+        })
+    }
 
-                // TODO: make votes HAMT as well
-                let mut votes = votes.clone();
-                votes.vote(voter);
-                let total = votes.total_votes();
+    fn clear_votes(&mut self, kind: NodeKind, reported: &PeerID) -> Result<(), Error> {
+        let (offline, cid) = self.offline(kind);
+        offline.with_mut(cid, |map| {
+            map.delete(&Self::key(reported))?;
+            Ok(())
+        })
+    }
 
-                map.set(reported_key, votes)?;
-                self.offline_checkers = map.flush()?;
+    fn subscribe(&mut self, subscriber: ActorID, method: MethodNum) -> Result<(), Error> {
+        let key = u64_key(subscriber);
+        let subscribed = self.handles.subscribers.with(&self.subscribers, |map| Ok(map.contains_key(&key)?))?;
 
-                Ok(total)
+        if !subscribed {
+            if self.total_subscribers >= MAX_SUBSCRIBERS {
+                return Err(Error::TooManySubscribers);
             }
+            self.total_subscribers += 1;
         }
+
+        self.handles.subscribers.with_mut(&self.subscribers, |map| {
+            let subscription = match map.get(&key)? {
+                Some(s) => Subscription { method, ..s.clone() },
+                None => Subscription::new(subscriber, method),
+            };
+            map.set(key, subscription)?;
+            Ok(())
+        })
+    }
+
+    fn unsubscribe(&mut self, subscriber: &ActorID) -> Result<(), Error> {
+        self.handles.subscribers.with_mut(&self.subscribers, |map| {
+            map.delete(&u64_key(*subscriber))?.ok_or(Error::NotExists)?;
+            Ok(())
+        })?;
+        self.total_subscribers -= 1;
+        Ok(())
+    }
+
+    fn subscriptions(&self) -> Result<Vec<Subscription>, Error> {
+        self.handles.subscribers.with(&self.subscribers, |map| {
+            let mut subscriptions = vec![];
+            map.for_each(|_, s| {
+                subscriptions.push(s.clone());
+                Ok(())
+            })?;
+            Ok(subscriptions)
+        })
+    }
+
+    fn new_notification(&mut self, id: &PeerID, change: StatusChange, epoch: ChainEpoch) -> StatusNotification {
+        let seq = self.next_notification;
+        self.next_notification += 1;
+        StatusNotification { seq, namespace: self.namespace.clone(), id: id.clone(), change, epoch }
+    }
+
+    fn add_undelivered(&mut self, subscriber: &ActorID, notifications: Vec<StatusNotification>) -> Result<(), Error> {
+        let key = u64_key(*subscriber);
+        self.handles.subscribers.with_mut(&self.subscribers, |map| {
+            // the subscriber may have left in the meantime
+            if let Some(mut subscription) = map.get(&key)?.cloned() {
+                notifications.into_iter().for_each(|n| subscription.add_undelivered(n));
+                map.set(key, subscription)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn take_undelivered(&mut self, subscriber: &ActorID) -> Result<(MethodNum, Vec<StatusNotification>), Error> {
+        let key = u64_key(*subscriber);
+        self.handles.subscribers.with_mut(&self.subscribers, |map| {
+            let mut subscription = map.get(&key)?.cloned().ok_or(Error::NotExists)?;
+            let undelivered = std::mem::take(&mut subscription.undelivered);
+            let method = subscription.method;
+            map.set(key, subscription)?;
+            Ok((method, undelivered.into()))
+        })
     }
 
     fn total_checkers(&self) -> usize { self.total_checkers }
 
     fn vote_duration_threshold(&self) -> ChainEpoch { self.voting_duration }
 
-    fn load() -> Result<Self, Error> {
-        let root = fvm_sdk::sself::root()?;
-        (Blockstore.get_cbor::<Self>(&root)?).ok_or(Error::CannotDeserialize)
+    fn voting_mode(&self) -> &VotingMode { &self.voting_mode }
+
+    fn appeal_window(&self) -> ChainEpoch { self.appeal_window }
+
+    fn member_deposit(&self) -> &TokenAmount { &self.member_deposit }
+
+    fn quorum(&self) -> &Quorum { &self.quorum }
+
+    fn checkers_per_member(&self) -> usize { self.checkers_per_member as usize }
+
+    fn assignment_window(&self) -> ChainEpoch { self.assignment_window }
+
+    fn migrate() -> Result<(), Error> {
+        let root = runtime::root()?;
+        if let Ok(Some(r)) = Blockstore.get_cbor::<RootState>(&root) {
+            return match r.version {
+                STATE_VERSION => Ok(()),
+                1 => Self::migrate_v1(r),
+                v => Err(Error::StateVersion(v)),
+            };
+        }
+
+        let v0 = Blockstore.get_cbor::<StateV0>(&root)?.ok_or(Error::CannotDeserialize)?;
+        let s = Self::from_v0(v0)?;
+        let admins = s.checker_creators()?;
+        Self::ensure_migrator(&admins)?;
+        Self::init_root(admins)?;
+        s.save()?;
+        Ok(())
+    }
+
+    fn load(namespace: &NamespaceId) -> Result<Self, Error> {
+        let root = Self::load_root()?;
+        let map = get_map_from_cid::<_, HamtState>(&root.namespaces, &Blockstore{}, DEFAULT_HAMT_BIT_WIDTH)?;
+        let mut s = map
+            .get(&BytesKey::from(namespace.as_str()))?
+            .ok_or(Error::NotExists)?
+            .clone();
+        s.namespace = namespace.clone();
+        s.handles = Handles::new(s.hamt_bit_width);
+        Ok(s)
     }
 
     fn save(&self) -> Result<Cid, Error> {
-        let serialized = to_vec(self)?;
-        let cid = fvm_sdk::ipld::put(Code::Blake2b256.into(), 32, DAG_CBOR, serialized.as_slice())?;
-        fvm_sdk::sself::set_root(&cid)?;
-        Ok(cid)
+        let state = self.flushed()?;
+        let mut root = Self::load_root()?;
+        let mut map = get_map_from_cid::<_, HamtState>(&root.namespaces, &Blockstore{}, DEFAULT_HAMT_BIT_WIDTH)?;
+        map.set(BytesKey::from(self.namespace.as_str()), state)?;
+        root.namespaces = map.flush()?;
+        Self::save_root(&root)
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+    use crate::client::{export_car, load_car, StateReader};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use serde::de::DeserializeOwned;
+
+    const CHECKERS: [&str; 3] = [
+        "12D3KooWD3eckifWpRn9wQpMG9R9hX3sD158z7EqHWmweQAJU5SA",
+        "12D3KooWRBy97UB99e3J6hiPesre1MZeuNQvfan4gBziswrRJsNK",
+        "12D3KooWHKkHiNhZtKceQehHhPqwxPFsVtm4UeXxAM5aUsdWvD9d",
+    ];
+    const MEMBERS: [&str; 2] = [
+        "12D3KooWJWoaqZhDaoEFshF7Rh1bpY9ohihFhzcW6d69Lr2NASuq",
+        "12D3KooWCKCeqLPSgMnDjyFsJuWqREDtKNHx1JEBiwaMXhCLNTRb",
+    ];
+
+    fn flush<V: DeserializeOwned + Serialize + PartialEq>(
+        store: &MemoryBlockstore,
+        bit_width: u32,
+        entries: Vec<(BytesKey, V)>,
+    ) -> Cid {
+        let mut map = make_empty_map::<_, V>(store, bit_width);
+        for (k, v) in entries {
+            map.set(k, v).unwrap();
+        }
+        map.flush().unwrap()
+    }
+
+    fn node(id: &str, creator: ActorID) -> NodeInfo {
+        NodeInfo::from_v0(id.to_string(), creator, &[String::from("/ip4/10.0.0.1/tcp/4001")], 10)
+    }
+
+    /// A namespace with checkers, members, votes and subscribers in HAMTs
+    /// of `bit_width`, as the actor would store it.
+    fn populated(store: &MemoryBlockstore, namespace: &str, bit_width: u32) -> HamtState {
+        let checkers = CHECKERS.iter().enumerate().map(|(i, id)| (BytesKey::from(*id), node(id, 100 + i as u64)));
+        let members = MEMBERS.iter().map(|id| (BytesKey::from(*id), node(id, 200)));
+        let mut votes = Votes::new(12);
+        votes.votes.push(CHECKERS[0].to_string());
+        let mut subscription = Subscription::new(300, 2);
+        subscription.add_undelivered(StatusNotification {
+            seq: 0,
+            namespace: namespace.to_string(),
+            id: CHECKERS[2].to_string(),
+            change: StatusChange::CheckerJailed,
+            epoch: 12,
+        });
+        HamtState {
+            namespace: namespace.to_string(),
+            members: flush(store, bit_width, members.collect()),
+            checkers: flush(store, bit_width, checkers.collect()),
+            offline_checkers: flush(store, bit_width, vec![(BytesKey::from(CHECKERS[2]), votes)]),
+            offline_members: flush::<Votes>(store, bit_width, vec![]),
+            total_checkers: CHECKERS.len(),
+            voting_duration: DEFAULT_VOTING_DURATION,
+            voting_mode: VotingMode::Instant,
+            vote_commits: flush::<CommitRound>(store, bit_width, vec![]),
+            appeal_window: DEFAULT_APPEAL_WINDOW,
+            pending_evictions: flush::<PendingEviction>(store, bit_width, vec![]),
+            jail_cooldown: DEFAULT_JAIL_COOLDOWN,
+            quorum: Quorum::default(),
+            checkers_per_member: DEFAULT_CHECKERS_PER_MEMBER,
+            assignment_window: DEFAULT_ASSIGNMENT_WINDOW,
+            member_deposit: TokenAmount::from(1000),
+            total_members: MEMBERS.len() as u64,
+            max_members: None,
+            max_members_per_creator: Some(10),
+            member_counts: flush(store, bit_width, vec![(u64_key(200), MEMBERS.len() as u64)]),
+            subscribers: flush(store, bit_width, vec![(u64_key(300), subscription)]),
+            total_subscribers: 1,
+            next_notification: 1,
+            hamt_bit_width: bit_width,
+            handles: Handles::new(bit_width),
+        }
+    }
+
+    #[test]
+    fn unflushed_changes_are_saved_and_seen_by_clones() {
+        runtime::mock::reset();
+        HamtState::init_root(vec![100]).unwrap();
+        let namespace = NamespaceId::from(DEFAULT_NAMESPACE);
+        let checkers = CHECKERS.iter().map(|c| node(c, 100)).collect();
+        HamtState::new(&namespace, checkers, &InitParams::default()).unwrap().save().unwrap();
+
+        let mut s = HamtState::load(&namespace).unwrap();
+        runtime::mock::set_caller(200);
+        s.upsert_node(node(MEMBERS[0], 200)).unwrap();
+        s.total_members += 1;
+        let copy = s.clone();
+        assert!(copy.is_member(&MEMBERS[0].to_string()).unwrap());
+        assert_eq!(copy, s);
+        assert_ne!(HamtState::load(&namespace).unwrap(), s);
+
+        s.save().unwrap();
+        let loaded = HamtState::load(&namespace).unwrap();
+        assert!(loaded.is_member(&MEMBERS[0].to_string()).unwrap());
+        assert_eq!(loaded, s);
+        assert_eq!(loaded, copy);
+        assert_eq!(loaded.members, copy.flushed().unwrap().members);
+    }
+
+    #[test]
+    fn populated_state_round_trips_through_car() {
+        let store = MemoryBlockstore::new();
+        let states = vec![populated(&store, DEFAULT_NAMESPACE, DEFAULT_HAMT_BIT_WIDTH), populated(&store, "rpc", 3)];
+        let namespaces = flush(
+            &store,
+            DEFAULT_HAMT_BIT_WIDTH,
+            states.iter().map(|s| (BytesKey::from(s.namespace.as_str()), s.clone())).collect(),
+        );
+        let root = RootState { version: STATE_VERSION, namespaces, admins: vec![100, 101] };
+        let root = store.put_cbor(&root, Code::Blake2b256).unwrap();
+
+        let mut car = vec![];
+        let blocks = export_car(&store, &root, &mut car).unwrap();
+        let (loaded, roots) = load_car(&car[..]).unwrap();
+        assert_eq!(roots, vec![root]);
+        // exporting the loaded blocks gives the same file
+        let mut again = vec![];
+        assert_eq!(export_car(&loaded, &root, &mut again).unwrap(), blocks);
+        assert_eq!(again, car);
+
+        let reader = StateReader::new(loaded, &root).unwrap();
+        assert_eq!(reader.admins(), &[100, 101]);
+        let mut namespaces = reader.namespaces().unwrap();
+        namespaces.sort();
+        assert_eq!(namespaces, vec![DEFAULT_NAMESPACE.to_string(), String::from("rpc")]);
+
+        for s in states {
+            let r = reader.namespace(&s.namespace).unwrap();
+            assert_eq!(r.state(), &s);
+            assert_eq!(r.hamt_bit_width(), s.hamt_bit_width);
+
+            let mut checkers: Vec<PeerID> = r.checkers().unwrap().iter().map(|n| n.id().clone()).collect();
+            checkers.sort();
+            let mut expected: Vec<PeerID> = CHECKERS.iter().map(|c| c.to_string()).collect();
+            expected.sort();
+            assert_eq!(checkers, expected);
+            assert_eq!(r.members().unwrap().len(), MEMBERS.len());
+            assert_eq!(r.offline_votes(NodeKind::Checker).unwrap().len(), 1);
+            assert_eq!(r.member_counts().unwrap(), vec![(200, MEMBERS.len() as u64)]);
+            let subscribers = r.subscribers().unwrap();
+            assert_eq!(subscribers.len(), 1);
+            assert_eq!((subscribers[0].actor, subscribers[0].undelivered.len()), (300, 1));
+        }
     }
 }
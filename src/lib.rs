@@ -1,42 +1,73 @@
 mod actor;
+mod assignment;
 mod blockstore;
 mod error;
 mod hamt_state;
+mod migration;
+mod p2p;
+mod runtime;
 mod traits;
 mod types;
 mod util;
 
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub mod client;
+
 use crate::actor::Actor;
 use crate::error::Error;
 use crate::traits::UptimeCheckerActor;
-use crate::util::parse_params_or_abort;
+use crate::util::{parse_cbor_params_or_abort, parse_params_or_abort, return_cbor};
 use fvm_sdk::NO_DATA_BLOCK_ID;
 use crate::hamt_state::HamtState;
 
 /// The actor's WASM entrypoint. It takes the ID of the parameters block,
 /// and returns the ID of the return value block, or NO_DATA_BLOCK_ID if no
-/// return value.
+/// return value. Return values are CBOR encoded.
 #[no_mangle]
 pub fn invoke(params_block_id: u32) -> u32 {
     let params = match fvm_sdk::message::params_raw(params_block_id) {
         Ok(v) => v.1,
-        Err(e) => fvm_sdk::vm::abort(e as u32, Some(e.to_string().as_str())),
+        Err(e) => {
+            let e = Error::from(e);
+            fvm_sdk::vm::abort(e.code(), Some(e.to_string().as_str()))
+        }
     };
 
     let r = match fvm_sdk::message::method_number() {
-        1 => Actor::<HamtState>::init(parse_params_or_abort(&params)),
-        2 => Actor::<HamtState>::new_checker(parse_params_or_abort(&params)),
-        3 => Actor::<HamtState>::new_member(parse_params_or_abort(&params)),
-        4 => Actor::<HamtState>::edit_checker(parse_params_or_abort(&params)),
-        5 => Actor::<HamtState>::edit_member(parse_params_or_abort(&params)),
-        6 => Actor::<HamtState>::rm_checker(),
-        7 => Actor::<HamtState>::rm_member(),
-        8 => Actor::<HamtState>::report_checker(parse_params_or_abort(&params)),
-        _ => Ok(()),
+        1 => Actor::<HamtState>::init(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        2 => Actor::<HamtState>::new_checker(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        3 => Actor::<HamtState>::new_member(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        4 => Actor::<HamtState>::edit_checker(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        5 => Actor::<HamtState>::edit_member(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        6 => Actor::<HamtState>::rm_checker(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        7 => Actor::<HamtState>::rm_member(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        8 => Actor::<HamtState>::report_checker(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        9 => Actor::<HamtState>::transfer_ownership(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        10 => Actor::<HamtState>::accept_ownership(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        11 => Actor::<HamtState>::report_member(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        12 => Actor::<HamtState>::get_assignments(parse_params_or_abort(&params)).and_then(return_cbor),
+        13 => Actor::<HamtState>::commit_report(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        14 => Actor::<HamtState>::reveal_report(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        15 => Actor::<HamtState>::finalize_report(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        16 => Actor::<HamtState>::appeal_eviction(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        17 => Actor::<HamtState>::vouch_online(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        18 => Actor::<HamtState>::finalize_eviction(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        19 => Actor::<HamtState>::unjail(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        20 => Actor::<HamtState>::subscribe(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        21 => Actor::<HamtState>::unsubscribe(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        22 => Actor::<HamtState>::member_status(parse_cbor_params_or_abort(&params)).and_then(return_cbor),
+        23 => Actor::<HamtState>::member_uptime(parse_cbor_params_or_abort(&params)).and_then(return_cbor),
+        24 => Actor::<HamtState>::checker_status(parse_cbor_params_or_abort(&params)).and_then(return_cbor),
+        25 => Actor::<HamtState>::new_namespace(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        26 => Actor::<HamtState>::migrate().map(|_| NO_DATA_BLOCK_ID),
+        27 => Actor::<HamtState>::register_members_batch(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        28 => Actor::<HamtState>::report_checkers_batch(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        29 => Actor::<HamtState>::deliver_notifications(parse_params_or_abort(&params)).map(|_| NO_DATA_BLOCK_ID),
+        _ => Ok(NO_DATA_BLOCK_ID),
     };
 
     match r {
-        Ok(_) => NO_DATA_BLOCK_ID,
-        Err(e) => fvm_sdk::vm::abort(e.code(), Some(e.msg().as_str())),
+        Ok(block_id) => block_id,
+        Err(e) => fvm_sdk::vm::abort(e.code(), Some(e.to_string().as_str())),
     }
 }
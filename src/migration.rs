@@ -0,0 +1,318 @@
+use crate::blockstore::{get_map_from_cid, make_empty_map, u64_key};
+use crate::types::{MultiAddr, NodeInfo, PeerID, DEFAULT_HAMT_BIT_WIDTH};
+use crate::Error;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::ActorID;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The version of the state layout written by this code. Any other
+/// version must go through `migrate` before the actor can be used.
+///
+/// - 0: a single registry at the root, nodes keyed by creator
+/// - 1: a registry per namespace, nodes keyed by PeerID
+/// - 2: ActorIDs keyed by their varint encoding rather than their decimal
+///   string, and a HAMT bit width per namespace
+pub const STATE_VERSION: u64 = 2;
+
+/// The fields of the version 0 state object that are carried over. It
+/// had no version tag. Its `offline_checkers` votes were cast by ActorID
+/// and cannot be converted, and `total_checkers` is recomputed.
+#[derive(Debug, Deserialize)]
+pub struct StateV0 {
+    pub members: Cid, // HAMT<BytesKey from ActorID, NodeInfoV0>
+    pub checkers: Cid, // HAMT<BytesKey from ActorID, NodeInfoV0>
+    pub voting_duration: ChainEpoch,
+}
+
+/// The version 0 node information
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct NodeInfoV0 {
+    pub id: PeerID,
+    pub creator: ActorID,
+    pub addresses: Vec<MultiAddr>,
+}
+
+/// Converts the nodes of a version 0 map, registered at `epoch`. A
+/// creator could only hold one node, but nothing stopped two creators
+/// from registering the same PeerID, so only the first node of each
+/// PeerID is kept.
+pub fn nodes_v0<BS: Blockstore>(store: &BS, map_cid: &Cid, epoch: ChainEpoch) -> Result<Vec<NodeInfo>, Error> {
+    let map = get_map_from_cid::<_, NodeInfoV0>(map_cid, store, DEFAULT_HAMT_BIT_WIDTH)?;
+    let mut seen = HashSet::new();
+    let mut nodes = vec![];
+    map.for_each(|_, n| {
+        if seen.insert(n.id.clone()) {
+            nodes.push(NodeInfo::from_v0(n.id.clone(), n.creator, &n.addresses, epoch));
+        }
+        Ok(())
+    })?;
+    Ok(nodes)
+}
+
+/// Copies a version 1 map keyed by decimal ActorIDs into a new map keyed
+/// by `u64_key`, and returns its root. Version 1 maps all had the default
+/// bit width.
+pub fn rekey_v1<BS, V>(store: &BS, map_cid: &Cid) -> Result<Cid, Error>
+where
+    BS: Blockstore,
+    V: DeserializeOwned + Serialize + Clone + PartialEq,
+{
+    let map = get_map_from_cid::<_, V>(map_cid, store, DEFAULT_HAMT_BIT_WIDTH)?;
+    let mut rekeyed = make_empty_map::<_, V>(store, DEFAULT_HAMT_BIT_WIDTH);
+    map.for_each(|k, v| {
+        let id = std::str::from_utf8(&k.0)
+            .ok()
+            .and_then(|k| k.parse::<ActorID>().ok())
+            .ok_or_else(|| anyhow::anyhow!("invalid version 1 key {:?}", k.0))?;
+        rekeyed.set(u64_key(id), v.clone())?;
+        Ok(())
+    })?;
+    Ok(rekeyed.flush()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hamt_state::{HamtState, RootState};
+    use crate::runtime::mock;
+    use crate::traits::LoadableState;
+    use crate::types::{InitParams, NamespaceId, NodeKind, Subscription, DEFAULT_NAMESPACE};
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::CborStore;
+    use fvm_ipld_hamt::BytesKey;
+
+    const A: &str = "12D3KooWD3eckifWpRn9wQpMG9R9hX3sD158z7EqHWmweQAJU5SA";
+    const B: &str = "12D3KooWRBy97UB99e3J6hiPesre1MZeuNQvfan4gBziswrRJsNK";
+    const C: &str = "12D3KooWHKkHiNhZtKceQehHhPqwxPFsVtm4UeXxAM5aUsdWvD9d";
+
+    /// The version 0 offline votes, cast by ActorID
+    #[derive(Serialize, Deserialize, PartialEq)]
+    struct VotesV0 {
+        last_vote: ChainEpoch,
+        votes: Vec<ActorID>,
+    }
+
+    /// The whole version 0 state object
+    #[derive(Serialize)]
+    struct StoredV0 {
+        members: Cid,
+        checkers: Cid,
+        offline_checkers: Cid,
+        total_checkers: usize,
+        voting_duration: ChainEpoch,
+    }
+
+    fn node_v0(id: &str, creator: ActorID, addresses: &[&str]) -> NodeInfoV0 {
+        NodeInfoV0 { id: id.to_string(), creator, addresses: addresses.iter().map(|a| a.to_string()).collect() }
+    }
+
+    /// Stores a version 0 state as the first deployments did, and returns
+    /// its root: checkers of A by creators 100 and 101, of B by 102, a
+    /// member C of creator 100, and an open offline vote on 102. Nodes
+    /// are keyed by creator. `tests/fixtures/state_v0.car` is its export.
+    fn store_v0<BS: Blockstore>(store: &BS) -> Cid {
+        let mut checkers = make_empty_map::<_, NodeInfoV0>(store, DEFAULT_HAMT_BIT_WIDTH);
+        checkers.set(BytesKey::from("100"), node_v0(A, 100, &["/ip4/10.0.0.1/tcp/4001", "not a multiaddr"])).unwrap();
+        // registered twice, by another creator
+        checkers.set(BytesKey::from("101"), node_v0(A, 101, &["/ip4/10.0.0.2/tcp/4001"])).unwrap();
+        checkers.set(BytesKey::from("102"), node_v0(B, 102, &["/dns4/checker.example.com/tcp/443/wss"])).unwrap();
+        let mut members = make_empty_map::<_, NodeInfoV0>(store, DEFAULT_HAMT_BIT_WIDTH);
+        members.set(BytesKey::from("100"), node_v0(C, 100, &["/ip4/10.0.1.1/udp/4001/quic"])).unwrap();
+        let mut offline = make_empty_map::<_, VotesV0>(store, DEFAULT_HAMT_BIT_WIDTH);
+        offline.set(BytesKey::from("102"), VotesV0 { last_vote: 10, votes: vec![100] }).unwrap();
+        let state = StoredV0 {
+            members: members.flush().unwrap(),
+            checkers: checkers.flush().unwrap(),
+            offline_checkers: offline.flush().unwrap(),
+            total_checkers: 3,
+            voting_duration: 300,
+        };
+        store.put_cbor(&state, Code::Blake2b256).unwrap()
+    }
+
+    fn state_v0() -> (MemoryBlockstore, StateV0) {
+        let store = MemoryBlockstore::new();
+        let root = store_v0(&store);
+        let v0 = store.get_cbor::<StateV0>(&root).unwrap().unwrap();
+        (store, v0)
+    }
+
+    fn node(id: &str, creator: ActorID, addresses: &[&str], epoch: ChainEpoch) -> NodeInfo {
+        let addresses: Vec<MultiAddr> = addresses.iter().map(|a| a.to_string()).collect();
+        NodeInfo::from_v0(id.to_string(), creator, &addresses, epoch)
+    }
+
+    /// The fixture the client tests load is the state `store_v0` stores
+    #[cfg(feature = "client")]
+    #[test]
+    fn v0_fixture_is_the_stored_state() {
+        let fixture: &[u8] = include_bytes!("../tests/fixtures/state_v0.car");
+        let (store, roots) = crate::client::load_car(fixture).unwrap();
+        assert_eq!(roots, vec![store_v0(&MemoryBlockstore::new())]);
+
+        let mut exported = vec![];
+        crate::client::export_car(&store, &roots[0], &mut exported).unwrap();
+        assert_eq!(exported, fixture);
+    }
+
+    #[test]
+    fn v0_state_converts_to_current_nodes() {
+        let (store, v0) = state_v0();
+        assert_eq!(v0.voting_duration, 300);
+
+        let members = nodes_v0(&store, &v0.members, 7).unwrap();
+        assert_eq!(members, vec![node(C, 100, &["/ip4/10.0.1.1/udp/4001/quic"], 7)]);
+        // tracked from the migration on
+        assert_eq!(members[0].uptime(0, 10), (3, 3));
+
+        let mut checkers = nodes_v0(&store, &v0.checkers, 7).unwrap();
+        checkers.sort_by(|a, b| a.id().cmp(b.id()));
+        assert_eq!(checkers.len(), 2);
+        // either registration of A is kept, the invalid address is dropped
+        let a = match checkers[0].creator() {
+            100 => node(A, 100, &["/ip4/10.0.0.1/tcp/4001"], 7),
+            _ => node(A, 101, &["/ip4/10.0.0.2/tcp/4001"], 7),
+        };
+        assert_eq!(checkers[0], a);
+        assert_eq!(checkers[1], node(B, 102, &["/dns4/checker.example.com/tcp/443/wss"], 7));
+    }
+
+    #[test]
+    fn v0_nodes_round_trip_in_the_current_layout() {
+        let (store, v0) = state_v0();
+        for n in nodes_v0(&store, &v0.checkers, 7).unwrap() {
+            let bytes = fvm_ipld_encoding::to_vec(&n).unwrap();
+            assert_eq!(fvm_ipld_encoding::from_slice::<NodeInfo>(&bytes).unwrap(), n);
+        }
+    }
+
+    #[test]
+    fn v0_states_upgrade_to_the_current_layout() {
+        mock::reset();
+        mock::set_epoch(7);
+        let root = store_v0(&crate::blockstore::Blockstore);
+        mock::with(|m| m.root = Some(root));
+
+        // only the creators of the checkers kept may migrate
+        mock::set_caller(999);
+        assert!(matches!(HamtState::migrate(), Err(Error::NotCaller)));
+        assert_eq!(mock::with(|m| m.root), Some(root));
+        mock::set_caller(102);
+        HamtState::migrate().unwrap();
+
+        assert!(HamtState::is_admin(&102).unwrap());
+        assert!(!HamtState::is_admin(&999).unwrap());
+        let s = HamtState::load(&NamespaceId::from(DEFAULT_NAMESPACE)).unwrap();
+        assert_eq!((s.total_checkers, s.total_members, s.voting_duration), (2, 1, 300));
+        assert!(s.is_checker(&B.to_string(), &102).unwrap());
+        assert!(s.is_member(&C.to_string()).unwrap());
+        assert_eq!(s.node(NodeKind::Member, &C.to_string()).unwrap().unwrap(), node(C, 100, &["/ip4/10.0.1.1/udp/4001/quic"], 7));
+        // the votes cast by ActorID are dropped
+        assert!(!s.has_voted(NodeKind::Checker, &B.to_string(), &A.to_string()).unwrap());
+
+        // migrating again does nothing
+        let migrated = mock::with(|m| m.root);
+        HamtState::migrate().unwrap();
+        assert_eq!(mock::with(|m| m.root), migrated);
+    }
+
+    /// Stores a version 1 state in the mock runtime: the default namespace
+    /// with checkers A of creator 100 and B of 102, 2 members of creator
+    /// 100 and a subscriber 300, both keyed by decimal ActorID.
+    fn store_v1(admins: Vec<ActorID>) {
+        let store = &crate::blockstore::Blockstore;
+        let namespace = NamespaceId::from(DEFAULT_NAMESPACE);
+        mock::reset();
+        HamtState::init_root(admins.clone()).unwrap();
+        let checkers = vec![node(A, 100, &["/ip4/10.0.0.1/tcp/4001"], 0), node(B, 102, &["/ip4/10.0.0.2/tcp/4001"], 0)];
+        let mut s = HamtState::new(&namespace, checkers, &InitParams::default()).unwrap();
+
+        let mut counts = make_empty_map::<_, u64>(store, DEFAULT_HAMT_BIT_WIDTH);
+        counts.set(BytesKey::from("100"), 2).unwrap();
+        let mut subscribers = make_empty_map::<_, Subscription>(store, DEFAULT_HAMT_BIT_WIDTH);
+        subscribers.set(BytesKey::from("300"), Subscription::new(300, 42)).unwrap();
+        s.member_counts = counts.flush().unwrap();
+        s.subscribers = subscribers.flush().unwrap();
+        s.total_subscribers = 1;
+        s.save().unwrap();
+
+        let root = store.get_cbor::<RootState>(&mock::with(|m| m.root).unwrap()).unwrap().unwrap();
+        let root = RootState { version: 1, admins, ..root };
+        let root = store.put_cbor(&root, Code::Blake2b256).unwrap();
+        mock::with(|m| m.root = Some(root));
+    }
+
+    #[test]
+    fn v1_states_upgrade_to_the_current_layout() {
+        let namespace = NamespaceId::from(DEFAULT_NAMESPACE);
+        // the admins of the state are kept, the checker creators are not admins
+        store_v1(vec![500]);
+        mock::set_caller(100);
+        assert!(matches!(HamtState::load(&namespace), Err(Error::StateVersion(1))));
+        assert!(matches!(HamtState::migrate(), Err(Error::NotCaller)));
+        mock::set_caller(500);
+        HamtState::migrate().unwrap();
+        assert!(HamtState::is_admin(&500).unwrap());
+        assert!(!HamtState::is_admin(&100).unwrap());
+
+        let s = HamtState::load(&namespace).unwrap();
+        let store = &crate::blockstore::Blockstore;
+        let counts = get_map_from_cid::<_, u64>(&s.member_counts, store, DEFAULT_HAMT_BIT_WIDTH).unwrap();
+        assert_eq!(counts.get(&u64_key(100)).unwrap(), Some(&2));
+        assert_eq!(counts.get(&BytesKey::from("100")).unwrap(), None);
+        assert_eq!(s.subscriptions().unwrap(), vec![Subscription::new(300, 42)]);
+        assert!(s.is_checker(&B.to_string(), &102).unwrap());
+
+        // migrating again does nothing
+        let migrated = mock::with(|m| m.root);
+        HamtState::migrate().unwrap();
+        assert_eq!(mock::with(|m| m.root), migrated);
+
+        // states stored before there were admins get the checker creators
+        store_v1(vec![]);
+        mock::set_caller(500);
+        assert!(matches!(HamtState::migrate(), Err(Error::NotCaller)));
+        mock::set_caller(102);
+        HamtState::migrate().unwrap();
+        for admin in [100, 102] {
+            assert!(HamtState::is_admin(&admin).unwrap());
+        }
+        assert!(!HamtState::is_admin(&500).unwrap());
+    }
+
+    #[test]
+    fn rekey_v1_keys_actor_ids_by_varint() {
+        let store = MemoryBlockstore::new();
+        let mut v1 = make_empty_map::<_, u64>(&store, DEFAULT_HAMT_BIT_WIDTH);
+        for id in [0u64, 100, 1 << 40] {
+            v1.set(BytesKey::from(id.to_string().as_str()), id + 1).unwrap();
+        }
+        let root = rekey_v1::<_, u64>(&store, &v1.flush().unwrap()).unwrap();
+
+        let map = get_map_from_cid::<_, u64>(&root, &store, DEFAULT_HAMT_BIT_WIDTH).unwrap();
+        let mut count = 0;
+        map.for_each(|_, _| {
+            count += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(count, 3);
+        for id in [0u64, 100, 1 << 40] {
+            assert_eq!(map.get(&u64_key(id)).unwrap(), Some(&(id + 1)));
+            assert_eq!(map.get(&BytesKey::from(id.to_string().as_str())).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn rekey_v1_rejects_keys_that_are_not_actor_ids() {
+        let store = MemoryBlockstore::new();
+        let mut v1 = make_empty_map::<_, u64>(&store, DEFAULT_HAMT_BIT_WIDTH);
+        v1.set(BytesKey::from("f0100"), 1).unwrap();
+        assert!(rekey_v1::<_, u64>(&store, &v1.flush().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,279 @@
+use crate::Error;
+use cid::multihash::Multihash;
+use cid::Cid;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Longest accepted PeerID string. Base58 sha256 ids are 46 chars and
+/// CIDv1 encoded ones are around 60, this leaves room for other bases.
+pub const MAX_PEER_ID_LEN: usize = 128;
+/// Longest accepted multiaddr string
+pub const MAX_MULTIADDR_LEN: usize = 256;
+/// Maximum number of multiaddrs a node can register
+pub const MAX_ADDRESSES: usize = 16;
+
+/// Multicodec of libp2p public keys, used by CID encoded PeerIDs
+const LIBP2P_KEY_CODEC: u64 = 0x72;
+const IDENTITY_CODE: u64 = 0x00;
+const SHA2_256_CODE: u64 = 0x12;
+/// libp2p inlines keys up to 42 bytes with the identity hash
+const MAX_INLINE_KEY_LEN: u8 = 42;
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Parses a libp2p PeerID, either in its legacy base58btc multihash form
+/// (`Qm...`, `12D3Koo...`) or as a CIDv1 with the libp2p-key codec.
+pub fn parse_peer_id(s: &str) -> Result<Multihash, Error> {
+    let err = || Error::InvalidPeerID(s.to_string());
+    if s.is_empty() || s.len() > MAX_PEER_ID_LEN {
+        return Err(err());
+    }
+
+    let hash = if s.starts_with('1') || s.starts_with("Qm") {
+        let bytes = decode_base58btc(s).ok_or_else(err)?;
+        Multihash::from_bytes(&bytes).map_err(|_| err())?
+    } else {
+        let cid = Cid::try_from(s).map_err(|_| err())?;
+        if cid.codec() != LIBP2P_KEY_CODEC {
+            return Err(err());
+        }
+        *cid.hash()
+    };
+
+    match hash.code() {
+        IDENTITY_CODE if hash.size() <= MAX_INLINE_KEY_LEN => Ok(hash),
+        SHA2_256_CODE if hash.size() == 32 => Ok(hash),
+        _ => Err(err()),
+    }
+}
+
+/// The value each multiaddr protocol carries after its name
+enum Arg {
+    None,
+    Ip4,
+    Ip6,
+    Port,
+    Str,
+    PeerID,
+}
+
+fn protocol(name: &str) -> Option<(u64, Arg)> {
+    let p = match name {
+        "ip4" => (0x04, Arg::Ip4),
+        "tcp" => (0x06, Arg::Port),
+        "dccp" => (0x21, Arg::Port),
+        "ip6" => (0x29, Arg::Ip6),
+        "dns" => (0x35, Arg::Str),
+        "dns4" => (0x36, Arg::Str),
+        "dns6" => (0x37, Arg::Str),
+        "dnsaddr" => (0x38, Arg::Str),
+        "sctp" => (0x84, Arg::Port),
+        "udp" => (0x0111, Arg::Port),
+        "p2p-circuit" => (0x0122, Arg::None),
+        "udt" => (0x012d, Arg::None),
+        "utp" => (0x012e, Arg::None),
+        "p2p" | "ipfs" => (0x01a5, Arg::PeerID),
+        "https" => (0x01bb, Arg::None),
+        "tls" => (0x01c0, Arg::None),
+        "noise" => (0x01c6, Arg::None),
+        "quic" => (0x01cc, Arg::None),
+        "quic-v1" => (0x01cd, Arg::None),
+        "webtransport" => (0x01d1, Arg::None),
+        "ws" => (0x01dd, Arg::None),
+        "wss" => (0x01de, Arg::None),
+        "http" => (0x01e0, Arg::None),
+        _ => return None,
+    };
+    Some(p)
+}
+
+/// Parses a multiaddr string, e.g. `/ip4/10.1.1.1/tcp/8080`, into its
+/// binary representation.
+pub fn parse_multiaddr(s: &str) -> Result<Vec<u8>, Error> {
+    let err = || Error::InvalidMultiAddr(s.to_string());
+    if s.len() > MAX_MULTIADDR_LEN || !s.starts_with('/') {
+        return Err(err());
+    }
+
+    let mut out = vec![];
+    let mut parts = s[1..].split('/');
+    while let Some(name) = parts.next() {
+        let (code, arg) = protocol(name).ok_or_else(err)?;
+        write_varint(code, &mut out);
+        match arg {
+            Arg::None => {}
+            Arg::Ip4 => {
+                let ip = parts.next().and_then(|v| v.parse::<Ipv4Addr>().ok()).ok_or_else(err)?;
+                out.extend_from_slice(&ip.octets());
+            }
+            Arg::Ip6 => {
+                let ip = parts.next().and_then(|v| v.parse::<Ipv6Addr>().ok()).ok_or_else(err)?;
+                out.extend_from_slice(&ip.octets());
+            }
+            Arg::Port => {
+                let port = parts.next().and_then(|v| v.parse::<u16>().ok()).ok_or_else(err)?;
+                out.extend_from_slice(&port.to_be_bytes());
+            }
+            Arg::Str => {
+                let v = parts.next().filter(|v| !v.is_empty()).ok_or_else(err)?;
+                write_varint(v.len() as u64, &mut out);
+                out.extend_from_slice(v.as_bytes());
+            }
+            Arg::PeerID => {
+                let v = parts.next().ok_or_else(err)?;
+                let bytes = parse_peer_id(v)?.to_bytes();
+                write_varint(bytes.len() as u64, &mut out);
+                out.extend_from_slice(&bytes);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn write_varint(mut v: u64, out: &mut Vec<u8>) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn decode_base58btc(s: &str) -> Option<Vec<u8>> {
+    // little endian big number, reversed at the end
+    let mut bytes: Vec<u8> = Vec::with_capacity(s.len());
+    for c in s.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|a| *a == c)? as u32;
+        for b in bytes.iter_mut() {
+            carry += (*b as u32) * 58;
+            *b = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    // leading '1's encode leading zero bytes
+    bytes.extend(s.bytes().take_while(|c| *c == b'1').map(|_| 0));
+    bytes.reverse();
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519_PEER_ID: &str = "12D3KooWH9fRAeD3DuTDS5KNi7MAsdpscbEGWJ1aV4qkF6fGi7Kq";
+    const RSA_PEER_ID: &str = "QmYyQSo1c1Ym7orWxLYvCrM2EmxFTANf8wXmmE7DWjhx5N";
+    /// `RSA_PEER_ID` as a libp2p-key CIDv1
+    const CID_PEER_ID: &str = "bafzbeie5745rpv2m6tjyuugywy4d5ewrqgqqhfnf445he3omzpjbx5xqxe";
+
+    #[test]
+    fn base58_decodes_known_values() {
+        assert_eq!(decode_base58btc(""), Some(vec![]));
+        assert_eq!(decode_base58btc("1"), Some(vec![0]));
+        assert_eq!(decode_base58btc("2"), Some(vec![1]));
+        assert_eq!(decode_base58btc("z"), Some(vec![57]));
+        assert_eq!(decode_base58btc("21"), Some(vec![58]));
+        assert_eq!(decode_base58btc("115Q"), Some(vec![0, 0, 255]));
+        // 0, O, I and l are not in the alphabet
+        for s in ["0", "O", "I", "l", "Qm0"] {
+            assert_eq!(decode_base58btc(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn varints_are_little_endian_groups_of_seven_bits() {
+        for (v, bytes) in [
+            (0, vec![0x00]),
+            (0x7f, vec![0x7f]),
+            (0x80, vec![0x80, 0x01]),
+            (300, vec![0xac, 0x02]),
+            (0x01a5, vec![0xa5, 0x03]),
+            (u64::MAX, vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]),
+        ] {
+            let mut out = vec![];
+            write_varint(v, &mut out);
+            assert_eq!(out, bytes, "{}", v);
+        }
+    }
+
+    #[test]
+    fn peer_ids_parse_in_every_encoding() {
+        let inline = parse_peer_id(ED25519_PEER_ID).unwrap();
+        assert_eq!(inline.code(), IDENTITY_CODE);
+        assert_eq!(inline.size(), 36);
+
+        let hashed = parse_peer_id(RSA_PEER_ID).unwrap();
+        assert_eq!(hashed.code(), SHA2_256_CODE);
+        assert_eq!(hashed.size(), 32);
+        assert_eq!(parse_peer_id(CID_PEER_ID).unwrap(), hashed);
+    }
+
+    #[test]
+    fn malformed_peer_ids_are_rejected() {
+        let too_long = "Q".repeat(MAX_PEER_ID_LEN + 1);
+        // a dag-pb CID, i.e. not the libp2p-key codec
+        let dag_pb = Cid::new_v1(0x70, parse_peer_id(RSA_PEER_ID).unwrap()).to_string();
+        for s in [
+            "",
+            too_long.as_str(),
+            // not base58
+            "Qm0yQSo1c1Ym7orWxLYvCrM2EmxFTANf8wXmmE7DWjhx5N",
+            // digest shorter than its declared length
+            &RSA_PEER_ID[..40],
+            // identity code followed by a truncated length varint
+            "13D",
+            // neither a base58 multihash nor a CID
+            "not-a-peer-id",
+            dag_pb.as_str(),
+        ] {
+            assert!(matches!(parse_peer_id(s), Err(Error::InvalidPeerID(v)) if v == s), "{}", s);
+        }
+    }
+
+    #[test]
+    fn multiaddrs_encode_to_their_binary_form() {
+        assert_eq!(
+            parse_multiaddr("/ip4/127.0.0.1/tcp/4001").unwrap(),
+            vec![0x04, 127, 0, 0, 1, 0x06, 0x0f, 0xa1]
+        );
+
+        let mut dns = vec![0x36, 11];
+        dns.extend_from_slice(b"example.com");
+        dns.extend_from_slice(&[0x06, 0x01, 0xbb]);
+        assert_eq!(parse_multiaddr("/dns4/example.com/tcp/443").unwrap(), dns);
+
+        let ip6 = parse_multiaddr("/ip6/::1/udp/4001/quic-v1").unwrap();
+        assert_eq!(ip6[0], 0x29);
+        assert_eq!(ip6[1..17], Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(ip6[17..], [0x91, 0x02, 0x0f, 0xa1, 0xcd, 0x03]);
+
+        let p2p = parse_multiaddr(&format!("/ip4/10.0.0.1/tcp/1/p2p/{}", RSA_PEER_ID)).unwrap();
+        assert_eq!(p2p[8..11], [0xa5, 0x03, 34]);
+        assert_eq!(p2p[11..], parse_peer_id(RSA_PEER_ID).unwrap().to_bytes());
+    }
+
+    #[test]
+    fn malformed_multiaddrs_are_rejected() {
+        let too_long = format!("/dns/{}", "a".repeat(MAX_MULTIADDR_LEN));
+        for s in [
+            "",
+            "ip4/127.0.0.1",
+            too_long.as_str(),
+            // unknown protocol
+            "/ip4/127.0.0.1/foo/1",
+            // missing or malformed values
+            "/ip4",
+            "/ip4/256.0.0.1",
+            "/ip6/127.0.0.1",
+            "/tcp/65536",
+            "/dns4//tcp/1",
+            "/p2p/not-a-peer-id",
+            // trailing slash, i.e. an empty protocol name
+            "/ip4/127.0.0.1/",
+        ] {
+            assert!(parse_multiaddr(s).is_err(), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! The syscalls the actor relies on. On chain they go to the FVM through
+//! the SDK, unit tests run the actor on the host against the in-memory
+//! `mock` runtime instead.
+
+use crate::Error;
+use cid::Cid;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::receipt::Receipt;
+use fvm_shared::{ActorID, MethodNum};
+
+#[cfg(not(test))]
+pub use self::fvm::*;
+#[cfg(test)]
+pub use self::mock::*;
+
+#[cfg(not(test))]
+mod fvm {
+    use super::*;
+
+    pub fn caller() -> ActorID {
+        fvm_sdk::message::caller()
+    }
+
+    pub fn curr_epoch() -> ChainEpoch {
+        fvm_sdk::network::curr_epoch()
+    }
+
+    pub fn value_received() -> TokenAmount {
+        fvm_sdk::message::value_received()
+    }
+
+    pub fn hash_blake2b(data: &[u8]) -> [u8; 32] {
+        fvm_sdk::crypto::hash_blake2b(data)
+    }
+
+    pub fn beacon_randomness(dst: i64, round: ChainEpoch, entropy: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(fvm_sdk::rand::get_beacon_randomness(dst, round, entropy)?.0)
+    }
+
+    pub fn resolve_address(address: &Address) -> Option<ActorID> {
+        fvm_sdk::actor::resolve_address(address)
+    }
+
+    pub fn root() -> Result<Cid, Error> {
+        Ok(fvm_sdk::sself::root()?)
+    }
+
+    pub fn set_root(cid: &Cid) -> Result<(), Error> {
+        Ok(fvm_sdk::sself::set_root(cid)?)
+    }
+
+    pub fn get_block(cid: &Cid) -> Result<Vec<u8>, Error> {
+        Ok(fvm_sdk::ipld::get(cid)?)
+    }
+
+    /// Stores a block under its 32 bytes `code` multihash.
+    pub fn put_block(code: u64, codec: u64, data: &[u8]) -> Result<Cid, Error> {
+        Ok(fvm_sdk::ipld::put(code, 32, codec, data)?)
+    }
+
+    pub fn send(to: &Address, method: MethodNum, params: RawBytes, value: TokenAmount) -> Result<Receipt, Error> {
+        Ok(fvm_sdk::send::send(to, method, params, value)?)
+    }
+}
+
+/// A single actor invocation context per test thread, with its own
+/// blocks, state root, address book and outbox.
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use cid::multihash::Multihash;
+    use fvm_shared::error::{ErrorNumber, ExitCode};
+    use std::cell::RefCell;
+    use std::collections::{HashMap, HashSet};
+
+    const BLAKE2B_256: u64 = 0xb220;
+
+    #[derive(Default)]
+    pub struct Mock {
+        pub caller: ActorID,
+        pub epoch: ChainEpoch,
+        pub value: TokenAmount,
+        pub root: Option<Cid>,
+        pub blocks: HashMap<Cid, Vec<u8>>,
+        /// Non ID addresses that resolve
+        pub addresses: HashMap<Address, ActorID>,
+        /// The messages sent, in order
+        pub sent: Vec<(Address, MethodNum, RawBytes, TokenAmount)>,
+        /// Actors whose methods fail when sent a message
+        pub failing: HashSet<ActorID>,
+        /// The gas the methods of an actor use, none by default
+        pub gas_used: HashMap<ActorID, i64>,
+    }
+
+    thread_local! {
+        static MOCK: RefCell<Mock> = RefCell::new(Mock::default());
+    }
+
+    /// Runs `f` on the runtime of the current test.
+    pub fn with<R>(f: impl FnOnce(&mut Mock) -> R) -> R {
+        MOCK.with(|m| f(&mut m.borrow_mut()))
+    }
+
+    /// Starts a test with an empty runtime.
+    pub fn reset() {
+        with(|m| *m = Mock::default());
+    }
+
+    pub fn set_caller(caller: ActorID) {
+        with(|m| m.caller = caller);
+    }
+
+    pub fn set_epoch(epoch: ChainEpoch) {
+        with(|m| m.epoch = epoch);
+    }
+
+    /// The messages sent since the last call.
+    pub fn take_sent() -> Vec<(Address, MethodNum, RawBytes, TokenAmount)> {
+        with(|m| std::mem::take(&mut m.sent))
+    }
+
+    pub fn caller() -> ActorID {
+        with(|m| m.caller)
+    }
+
+    pub fn curr_epoch() -> ChainEpoch {
+        with(|m| m.epoch)
+    }
+
+    pub fn value_received() -> TokenAmount {
+        with(|m| m.value.clone())
+    }
+
+    pub fn hash_blake2b(data: &[u8]) -> [u8; 32] {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(blake2b_simd::Params::new().hash_length(32).hash(data).as_bytes());
+        hash
+    }
+
+    /// Deterministic per round, like the drand beacon.
+    pub fn beacon_randomness(dst: i64, round: ChainEpoch, entropy: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut data = dst.to_be_bytes().to_vec();
+        data.extend_from_slice(&round.to_be_bytes());
+        data.extend_from_slice(entropy);
+        Ok(hash_blake2b(&data).to_vec())
+    }
+
+    pub fn resolve_address(address: &Address) -> Option<ActorID> {
+        address.id().ok().or_else(|| with(|m| m.addresses.get(address).copied()))
+    }
+
+    pub fn root() -> Result<Cid, Error> {
+        with(|m| m.root).ok_or(Error::FVMSharedErrorNum(ErrorNumber::IllegalOperation))
+    }
+
+    pub fn set_root(cid: &Cid) -> Result<(), Error> {
+        with(|m| m.root = Some(*cid));
+        Ok(())
+    }
+
+    pub fn get_block(cid: &Cid) -> Result<Vec<u8>, Error> {
+        with(|m| m.blocks.get(cid).cloned()).ok_or(Error::FVMSharedErrorNum(ErrorNumber::NotFound))
+    }
+
+    /// Blocks are always hashed with blake2b-256, as the actor does.
+    pub fn put_block(code: u64, codec: u64, data: &[u8]) -> Result<Cid, Error> {
+        assert_eq!(code, BLAKE2B_256, "unexpected multihash");
+        let hash = Multihash::wrap(BLAKE2B_256, &hash_blake2b(data)).unwrap();
+        let cid = Cid::new_v1(codec, hash);
+        with(|m| m.blocks.insert(cid, data.to_vec()));
+        Ok(cid)
+    }
+
+    pub fn send(to: &Address, method: MethodNum, params: RawBytes, value: TokenAmount) -> Result<Receipt, Error> {
+        let id = resolve_address(to).ok_or(Error::FVMSharedErrorNum(ErrorNumber::NotFound))?;
+        let exit_code = if with(|m| m.failing.contains(&id)) {
+            ExitCode::USR_ILLEGAL_STATE
+        } else {
+            with(|m| m.sent.push((*to, method, params, value)));
+            ExitCode::OK
+        };
+        let gas_used = with(|m| m.gas_used.get(&id).copied().unwrap_or_default());
+        Ok(Receipt { exit_code, return_data: RawBytes::default(), gas_used })
+    }
+}
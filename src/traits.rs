@@ -1,8 +1,17 @@
 use cid::Cid;
-use fvm_shared::ActorID;
+use fvm_shared::{ActorID, MethodNum};
 use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
 use crate::error::Error;
-use crate::types::{InitParams, NodeInfo, NodeInfoPayload, ReportPayload};
+use uptime_checker_interface::{
+    CheckerQuery, CheckerStatus, MemberQuery, MemberStatus, Uptime, UptimeQuery,
+};
+use crate::types::{
+    AcceptOwnershipPayload, AssignmentsPage, AssignmentsQuery, CheckersReportBatchPayload, CommitReportPayload,
+    CommitRound, InitParams, MembersBatchPayload, NamespaceId, NodeIdPayload, NodeInfo, NodeInfoPayload, NodeKind, PeerID,
+    PendingEviction, Quorum, ReportMemberPayload, ReportPayload, RevealReportPayload, StatusChange,
+    StatusNotification, SubscribePayload, SubscriberPayload, Subscription, TransferOwnershipPayload, VotingMode,
+};
 
 pub trait UptimeCheckerActor {
     /// Initializes the state of the uptime actor. It accepts
@@ -10,16 +19,27 @@ pub trait UptimeCheckerActor {
     /// the constructor. IPC subnets will potentially pre-populate
     /// this list with the initial validators of the subnet
     ///
+    /// The actor holds independent registries, called namespaces,
+    /// each with its own checkers, members and voting parameters.
+    /// Every other method acts on the namespace given in its params.
+    /// The constructor creates the first one, `DEFAULT_NAMESPACE`
+    /// unless `namespace` is set, see `new_namespace` for the others.
+    ///
+    /// The params are rejected unless every initial checker has a valid
+    /// and unique PeerID, its own creator and its addresses, and there
+    /// are at least `checkers_per_member` of them.
+    ///
     /// - methodNum: 1
-    /// - allowed callers: any account.
+    /// - allowed callers: the system or init actor.
     /// - impacted state: State for the uptime actor
-    /// is initialized.
+    ///   is initialized.
     fn init(params: InitParams) -> Result<(), Error>;
 
     /// Adds a new checker to the list of checkers.
     /// This method checks that a checker for that
     /// peerID is not registered yet, and that the owner
-    /// specified in CheckInfo is the message signer.
+    /// specified in CheckInfo is the message signer. The PeerID
+    /// and multiaddresses must be well formed.
     ///
     /// - methodNum: 2
     /// - allowed callers: any account.
@@ -29,7 +49,13 @@ pub trait UptimeCheckerActor {
     /// Adds a new member to the list of nodes to be checked.
     /// This method checks that a member for that
     /// peerID is not registered yet, and that the owner
-    /// specified in CheckInfo is the message signer.
+    /// specified in CheckInfo is the message signer. The PeerID
+    /// and multiaddresses must be well formed.
+    ///
+    /// The message must carry at least the `member_deposit`, which
+    /// is refunded by `rm_member`. Registration fails once the total
+    /// number of members, or the number of members of the creator,
+    /// reaches its configured cap.
     ///
     /// - methodNum: 3
     /// - allowed callers: any account.
@@ -38,54 +64,67 @@ pub trait UptimeCheckerActor {
 
     /// Edits the node information of a checker. The method
     /// checks that the owner of the peer is the one signing
-    /// the transaction. Owners are allowed to any information
-    /// for the peer (including the owner and the peerID).
+    /// the transaction. Owners are allowed to change the
+    /// addresses of the peer; use `transfer_ownership` to change
+    /// the owner. The checker must be registered.
     ///
     /// - methodNum: 4
     /// - allowed callers: owner of the peerID.
     /// - impacted state: edits the CheckInfo for the peerID
-    /// in checkers.
+    ///   in checkers.
     fn edit_checker(params: NodeInfoPayload) -> Result<(), Error>;
 
     /// Edits the node information of a member. The method
     /// checks that the owner of the peer is the one signing
-    /// the transaction. Owners are allowed to any information
-    /// for the peer (including the owner and the peerID).
+    /// the transaction. Owners are allowed to change the
+    /// addresses of the peer; use `transfer_ownership` to change
+    /// the owner. The member must be registered, see `new_member`.
+    /// An edit clears the offline verdict of the member, which is
+    /// online again until its checkers declare it offline anew.
     ///
     /// - methodNum: 5
     /// - allowed callers: owner of the peerID.
     /// - impacted state: edits the NodeInfo for the peerID
-    /// in members.
+    ///   in members.
     fn edit_member(params: NodeInfoPayload) -> Result<(), Error>;
 
-    /// Removes a checker from the list. Only the owner of
-    /// the PeerID is allowed to remove themselves from the list.
+    /// Removes the checker with the given PeerID from the list.
+    /// Only the owner of the PeerID is allowed to remove it. An
+    /// owner may register several checkers and remove them one by one.
+    /// Jailed checkers cannot be removed, nor checkers voted offline
+    /// until their eviction is finalized.
     ///
     /// - methodNum: 6
     /// - allowed callers: owner of the peerID.
     /// - impacted state: removes peerID from the checkers HAMT.
-    fn rm_checker() -> Result<(), Error>;
+    fn rm_checker(params: NodeIdPayload) -> Result<(), Error>;
 
-    /// Removes a member from the list. Only the owner of
-    /// the PeerID is allowed to remove themselves from the list.
+    /// Removes the member with the given PeerID from the list.
+    /// Only the owner of the PeerID is allowed to remove it. An
+    /// owner may register several members and remove them one by one.
+    /// The registration deposit is refunded to the owner.
     ///
     /// - methodNum: 7
     /// - allowed callers: owner of the peerID.
     /// - impacted state: removes peerID from the members HAMT.
-    fn rm_member() -> Result<(), Error>;
+    fn rm_member(params: NodeIdPayload) -> Result<(), Error>;
 
     /// Reports a checker for being offline. This registers
     /// a new offline vote for the checker with the specified
-    /// peerID and removes the peer from checkers if there are
-    /// > 2/3 votes. If the last offline vote is older than
-    /// > OFFLINE_COUNT_RESTART the previous votes are not
+    /// peerID and starts its eviction if the votes are strictly
+    /// above the configured quorum (2/3 by default). The checker
+    /// is only removed once its appeal window is over, see
+    /// `appeal_eviction`. If the last offline vote is older than
+    /// `OFFLINE_COUNT_RESTART` the previous votes are not
     /// considered and conveniently cleaned, and the new one is added
     /// as the first one (it would be unfair to collect votes for
     /// the whole history of the checker). Only checkers are allowed
-    /// to report other checkers for being offline. Checkers are
+    /// to report other checkers for being offline, and the caller
+    /// must own the `voter` checker it votes with. Checkers are
     /// allowed to vote as many times as they want to update the
     /// valule of last_vote (despite a single vote being registred
-    /// per peerID).
+    /// per peerID). The reported checker must be registered and not
+    /// jailed, failing with `NotExists` or `Jailed` otherwise.
     ///
     /// Before removing a checker from the checkers list,
     /// a sanity-check is performed verifying that the voters
@@ -94,40 +133,379 @@ pub trait UptimeCheckerActor {
     /// removing and adding their membership to forge new votes
     /// to force the removal of a specific checker.
     ///
+    /// Only available in the `Instant` voting mode, see `commit_report`
+    /// otherwise.
+    ///
     /// - methodNum: 8
     /// - allowed callers: checkers.
     /// - impacted state: offline_checkers is updated with either
-    /// a new peerID and a vote, or a new vote for a PeerID, and
-    /// it removes PeerID from checkers if the number of
-    /// votes > 2/3 checkers
+    ///   a new peerID and a vote, or a new vote for a PeerID, and
+    ///   pending_evictions gets the PeerID if
+    ///   votes * quorum.denominator > checkers * quorum.numerator
     fn report_checker(param: ReportPayload) -> Result<(), Error>;
+
+    /// Transfers the ownership of a checker or member to a new owner
+    /// without deregistering it, so its votes history is kept. If
+    /// `require_accept` is set, the transfer is only recorded as
+    /// pending and completes once the new owner calls `accept_ownership`.
+    /// A new transfer overrides any pending one.
+    ///
+    /// - methodNum: 9
+    /// - allowed callers: owner of the peerID.
+    /// - impacted state: updates the owner, or the pending owner, of
+    ///   the peerID in checkers or members.
+    fn transfer_ownership(params: TransferOwnershipPayload) -> Result<(), Error>;
+
+    /// Completes a pending two-step ownership transfer.
+    ///
+    /// - methodNum: 10
+    /// - allowed callers: pending owner of the peerID.
+    /// - impacted state: updates the owner of the peerID in checkers
+    ///   or members.
+    fn accept_ownership(params: AcceptOwnershipPayload) -> Result<(), Error>;
+
+    /// Reports a member for being offline. Each member is checked by
+    /// `checkers_per_member` checkers, reassigned every `assignment_window`
+    /// epochs from the beacon randomness (see `get_assignments`), and only
+    /// those checkers can report it. The member is declared offline once
+    /// the votes of its assigned checkers reach the quorum. The verdict is
+    /// cleared when the owner edits the member.
+    ///
+    /// - methodNum: 11
+    /// - allowed callers: checkers assigned to the member.
+    /// - impacted state: offline_members is updated with a new vote, and
+    ///   the member is flagged offline if the quorum is reached.
+    fn report_member(params: ReportMemberPayload) -> Result<(), Error>;
+
+    /// Returns the PeerIDs of the members assigned to a checker in
+    /// the assignment window of the given epoch, which must not be in
+    /// the future. The assignment is deterministic, so any checker can
+    /// recompute it off-chain.
+    ///
+    /// Every member scanned scores the checkers until the requested one
+    /// is out of its `checkers_per_member` lowest scores, so a call only
+    /// scans the members from `offset`, up to `MAX_ASSIGNMENT_SCORES`
+    /// scores. Neither the members after the page nor the ids of the
+    /// members before it are read. The page returns the offset to query
+    /// next, if any.
+    ///
+    /// - methodNum: 12
+    /// - allowed callers: any account.
+    /// - impacted state: none.
+    fn get_assignments(params: AssignmentsQuery) -> Result<AssignmentsPage, Error>;
+
+    /// Commits to a hidden verdict on a checker in the `CommitReveal`
+    /// voting mode, so later voters cannot copy earlier ones. The first
+    /// commitment on a checker opens a round whose commit phase lasts
+    /// `commit_duration` epochs. A round that is over is finalized before
+    /// a new one is opened.
+    ///
+    /// - methodNum: 13
+    /// - allowed callers: checkers, in the commit phase.
+    /// - impacted state: the commitment is added to the round in vote_commits.
+    fn commit_report(params: CommitReportPayload) -> Result<(), Error>;
+
+    /// Opens a commitment during the `reveal_duration` epochs following
+    /// the commit phase. The round is finalized as soon as every
+    /// commitment has been revealed.
+    ///
+    /// - methodNum: 14
+    /// - allowed callers: checkers that committed, in the reveal phase.
+    /// - impacted state: the verdict is added to the round in vote_commits.
+    fn reveal_report(params: RevealReportPayload) -> Result<(), Error>;
+
+    /// Finalizes a round after its reveal phase. Unrevealed commitments
+    /// are ignored. The checker eviction starts if the offline verdicts are
+    /// above the quorum of the revealed votes, and the revealed votes are
+    /// themselves above the quorum of all the checkers, so a handful of
+    /// reveals cannot remove a checker.
+    ///
+    /// - methodNum: 15
+    /// - allowed callers: any account.
+    /// - impacted state: the round is removed from vote_commits, and the
+    ///   checker added to pending_evictions if the quorum is reached.
+    fn finalize_report(params: NodeIdPayload) -> Result<(), Error>;
+
+    /// Files a counter-claim against the pending eviction of a
+    /// checker. It is only considered if enough other checkers
+    /// vouch for the checker with `vouch_online`.
+    ///
+    /// - methodNum: 16
+    /// - allowed callers: owner of the accused peerID, during the
+    ///   appeal window.
+    /// - impacted state: the pending eviction is marked as appealed.
+    fn appeal_eviction(params: NodeIdPayload) -> Result<(), Error>;
+
+    /// Votes that a checker pending eviction was seen online.
+    ///
+    /// - methodNum: 17
+    /// - allowed callers: checkers other than the accused one, during
+    ///   the appeal window.
+    /// - impacted state: the vote is added to the pending eviction.
+    fn vouch_online(params: ReportPayload) -> Result<(), Error>;
+
+    /// Closes the appeal window of a checker. The checker is jailed
+    /// unless it appealed and the checkers that saw it online are
+    /// enough to contest the quorum, i.e.
+    /// online * quorum.denominator >= checkers * (quorum.denominator - quorum.numerator)
+    ///
+    /// - methodNum: 18
+    /// - allowed callers: any account, once the appeal window is over.
+    /// - impacted state: the PeerID is removed from pending_evictions,
+    ///   and jailed in checkers if the appeal failed.
+    fn finalize_eviction(params: NodeIdPayload) -> Result<(), Error>;
+
+    /// Reactivates a jailed checker. Evicted checkers are jailed
+    /// rather than deleted, keeping their information and votes
+    /// history, and can neither vote, be assigned members nor be
+    /// removed while jailed. The cooldown starts at `jail_cooldown`
+    /// epochs and doubles with every strike.
+    ///
+    /// - methodNum: 19
+    /// - allowed callers: owner of the peerID, once the cooldown is over.
+    /// - impacted state: the checker is active again in checkers.
+    fn unjail(params: NodeIdPayload) -> Result<(), Error>;
+
+    /// Registers `subscriber` for status change notifications. Whenever
+    /// a member is declared offline or a checker is jailed, a
+    /// `StatusNotification` is sent to `method` of every subscriber once
+    /// the new status is saved. Each subscriber may use up to
+    /// `NOTIFICATION_GAS_LIMIT` gas per call, and a subscriber failing or
+    /// running over only misses its own notifications, which are kept for
+    /// `deliver_notifications`. Subscribers are only notified of the
+    /// changes after they subscribed, and their number is capped.
+    /// Subscribing again updates the method.
+    ///
+    /// Subscriptions are per namespace.
+    ///
+    /// - methodNum: 20
+    /// - allowed callers: admins, the creators of the initial checkers.
+    /// - impacted state: the subscriber is added to subscribers.
+    fn subscribe(params: SubscribePayload) -> Result<(), Error>;
+
+    /// Unregisters `subscriber` from status change notifications.
+    ///
+    /// - methodNum: 21
+    /// - allowed callers: the subscriber and admins.
+    /// - impacted state: the subscriber is removed from subscribers.
+    fn unsubscribe(params: SubscriberPayload) -> Result<(), Error>;
+
+    /// Returns whether a member is registered and currently online,
+    /// i.e. not declared offline by its assigned checkers since it was
+    /// registered or last edited by its owner. Being online is thus
+    /// attested by the owner until the checkers reach a new verdict,
+    /// see `member_uptime` for its history. Params and return values
+    /// are CBOR encoded, see `uptime_checker_interface`.
+    ///
+    /// - methodNum: 22
+    /// - allowed callers: any actor.
+    /// - impacted state: none.
+    fn member_status(params: MemberQuery) -> Result<MemberStatus, Error>;
+
+    /// Returns the uptime of a member over the last `epochs` epochs.
+    /// Params and return values are CBOR encoded, see
+    /// `uptime_checker_interface`.
+    ///
+    /// - methodNum: 23
+    /// - allowed callers: any actor.
+    /// - impacted state: none.
+    fn member_uptime(params: UptimeQuery) -> Result<Uptime, Error>;
+
+    /// Returns whether a checker is registered and active. Params and
+    /// return values are CBOR encoded, see `uptime_checker_interface`.
+    ///
+    /// - methodNum: 24
+    /// - allowed callers: any actor.
+    /// - impacted state: none.
+    fn checker_status(params: CheckerQuery) -> Result<CheckerStatus, Error>;
+
+    /// Creates a new namespace, e.g. to monitor another service with
+    /// different checkers and quorum parameters. It takes the same
+    /// params as the constructor, validated the same way, and fails
+    /// if the namespace exists. Namespace ids are first come, first
+    /// served, so only admins can create them.
+    ///
+    /// - methodNum: 25
+    /// - allowed callers: admins, the creators of the initial checkers.
+    /// - impacted state: the namespace is added to namespaces.
+    fn new_namespace(params: InitParams) -> Result<(), Error>;
+
+    /// Upgrades the state of an existing deployment to the layout of
+    /// the current code, which refuses to load any other version. It
+    /// does nothing if the state is up to date.
+    ///
+    /// From version 0, the registry becomes the `DEFAULT_NAMESPACE`,
+    /// nodes are re-keyed by PeerID and their addresses converted to
+    /// the binary form, dropping the invalid ones. The open offline
+    /// votes are dropped. From version 1, the member counts and
+    /// subscribers are re-keyed by varint encoded ActorID. The admins
+    /// of version 1 states are kept, states without admins get the
+    /// creators of the default namespace checkers.
+    ///
+    /// - methodNum: 26
+    /// - allowed callers: the admins of the migrated state.
+    /// - impacted state: the whole state is rewritten.
+    fn migrate() -> Result<(), Error>;
+
+    /// Registers many members of the caller at once, e.g. to onboard a
+    /// whole subnet, as `new_member` would one by one but loading and
+    /// saving the state once. The batch is all or nothing: if any member
+    /// is rejected, nothing is registered and the call fails with
+    /// `BatchFailed`, listing the error of every rejected member by index.
+    ///
+    /// The message must carry at least `member_deposit` per member. The
+    /// value is split evenly between the members, so that their refunds
+    /// add up to it: each member holds `value / n` and the first one also
+    /// holds the remainder `value % n`. At most `MAX_BATCH_SIZE` members
+    /// per call.
+    ///
+    /// - methodNum: 27
+    /// - allowed callers: any account.
+    /// - impacted state: members HAMT is updated.
+    fn register_members_batch(params: MembersBatchPayload) -> Result<(), Error>;
+
+    /// Reports many checkers offline at once with the same `voter`, as
+    /// `report_checker` would one by one but loading and saving the
+    /// state once. The batch is all or nothing, failing with
+    /// `BatchFailed` if any vote is rejected. At most `MAX_BATCH_SIZE`
+    /// checkers per call.
+    ///
+    /// - methodNum: 28
+    /// - allowed callers: checkers.
+    /// - impacted state: as `report_checker`, for every checker.
+    fn report_checkers_batch(params: CheckersReportBatchPayload) -> Result<(), Error>;
+
+    /// Sends the subscriber the notifications it did not receive when
+    /// they were sent, in order, as CBOR encoded `StatusNotification`
+    /// params. Those it fails to handle again are kept for the next call.
+    /// Only the latest `MAX_UNDELIVERED_NOTIFICATIONS` are kept, the
+    /// subscription counts the ones dropped.
+    ///
+    /// - methodNum: 29
+    /// - allowed callers: the subscriber.
+    /// - impacted state: the undelivered notifications of the subscriber.
+    fn deliver_notifications(params: SubscriberPayload) -> Result<(), Error>;
 }
 
 pub trait LoadableState {
-    fn new(nodes: Vec<NodeInfo>, voting_duration: &Option<ChainEpoch>) -> Result<Self, Error> where Self: Sized;
+    /// Stores an empty root, without any namespace.
+    fn init_root(admins: Vec<ActorID>) -> Result<(), Error> where Self: Sized;
+
+    fn is_admin(actor: &ActorID) -> Result<bool, Error> where Self: Sized;
+
+    fn has_namespace(namespace: &NamespaceId) -> Result<bool, Error> where Self: Sized;
+
+    /// Creates the state of a new namespace. It is only stored on `save`.
+    fn new(namespace: &NamespaceId, nodes: Vec<NodeInfo>, params: &InitParams) -> Result<Self, Error> where Self: Sized;
 
     fn upsert_node(&mut self, node: NodeInfo) -> Result<(), Error>;
 
-    fn remove_node(&mut self, id: &ActorID) -> Result<(), Error>;
+    /// Removes the member, returning it so its deposit can be refunded.
+    fn remove_node(&mut self, id: &PeerID) -> Result<NodeInfo, Error>;
+
+    fn node(&self, kind: NodeKind, id: &PeerID) -> Result<Option<NodeInfo>, Error>;
+
+    fn is_member(&self, id: &PeerID) -> Result<bool, Error>;
+
+    /// Flags the member as offline and closes its voting round.
+    fn set_member_offline(&mut self, id: &PeerID, epoch: ChainEpoch) -> Result<(), Error>;
+
+    /// Calls `f` on the ids of the active nodes of `kind`, in the order of
+    /// their HAMT, until it returns false. The rest of the HAMT is not read.
+    fn for_each_id<F>(&self, kind: NodeKind, f: F) -> Result<(), Error>
+    where
+        F: FnMut(&PeerID) -> bool;
+
+    fn checker_ids(&self) -> Result<Vec<PeerID>, Error>;
 
-    fn is_checker(&self, caller: &ActorID) -> Result<bool, Error>;
+    /// Checks that `checker` is a registered checker owned by `owner`.
+    fn is_checker(&self, checker: &PeerID, owner: &ActorID) -> Result<bool, Error>;
 
     fn upsert_checker(&mut self, node: NodeInfo) -> Result<(), Error>;
 
-    fn remove_checker(&mut self, id: &ActorID) -> Result<(), Error>;
+    fn remove_checker(&mut self, id: &PeerID) -> Result<(), Error>;
 
-    /// Removes the checker without performing owner check. Use with care.
-    fn remove_checker_unchecked(&mut self, id: &ActorID) -> Result<(), Error>;
+    /// Jails the checker without performing owner check, keeping its
+    /// information. Use with care. Returns false if there was no active
+    /// checker to jail.
+    fn jail_checker(&mut self, id: &PeerID) -> Result<bool, Error>;
 
-    fn has_voted(&self, reported: &ActorID, voter: &ActorID) -> Result<bool, Error>;
+    /// Reactivates a jailed checker once its cooldown is over. Only
+    /// the owner can do it.
+    fn unjail_checker(&mut self, id: &PeerID) -> Result<(), Error>;
 
-    fn record_voted(&mut self, reported: &ActorID, voter: &ActorID) -> Result<usize, Error>;
+    /// Moves the node to `new_owner`, or records it as pending if
+    /// `require_accept` is set. Only the current owner can do it.
+    fn transfer_ownership(
+        &mut self,
+        kind: NodeKind,
+        id: &PeerID,
+        new_owner: ActorID,
+        require_accept: bool,
+    ) -> Result<(), Error>;
+
+    /// Completes the pending transfer of the node to the caller.
+    fn accept_ownership(&mut self, kind: NodeKind, id: &PeerID) -> Result<(), Error>;
+
+    fn has_voted(&self, kind: NodeKind, reported: &PeerID, voter: &PeerID) -> Result<bool, Error>;
+
+    fn record_voted(&mut self, kind: NodeKind, reported: &PeerID, voter: &PeerID) -> Result<usize, Error>;
+
+    fn commit_round(&self, reported: &PeerID) -> Result<Option<CommitRound>, Error>;
+
+    /// Stores the round on `reported`, or deletes it if `None`.
+    fn set_commit_round(&mut self, reported: &PeerID, round: Option<CommitRound>) -> Result<(), Error>;
+
+    fn pending_eviction(&self, checker: &PeerID) -> Result<Option<PendingEviction>, Error>;
+
+    /// Stores the pending eviction of `checker`, or deletes it if `None`.
+    fn set_pending_eviction(&mut self, checker: &PeerID, pending: Option<PendingEviction>) -> Result<(), Error>;
+
+    /// Closes the offline voting round on `reported`.
+    fn clear_votes(&mut self, kind: NodeKind, reported: &PeerID) -> Result<(), Error>;
+
+    fn subscribe(&mut self, subscriber: ActorID, method: MethodNum) -> Result<(), Error>;
+
+    fn unsubscribe(&mut self, subscriber: &ActorID) -> Result<(), Error>;
+
+    /// At most `MAX_SUBSCRIBERS` subscriptions.
+    fn subscriptions(&self) -> Result<Vec<Subscription>, Error>;
+
+    /// Numbers the status change of `id` for the subscribers.
+    fn new_notification(&mut self, id: &PeerID, change: StatusChange, epoch: ChainEpoch) -> StatusNotification;
+
+    /// Keeps the notifications `subscriber` did not receive, unless it
+    /// unsubscribed.
+    fn add_undelivered(&mut self, subscriber: &ActorID, notifications: Vec<StatusNotification>) -> Result<(), Error>;
+
+    /// The method of `subscriber` and the notifications it did not
+    /// receive, which are removed from its subscription.
+    fn take_undelivered(&mut self, subscriber: &ActorID) -> Result<(MethodNum, Vec<StatusNotification>), Error>;
 
     fn total_checkers(&self) -> usize;
 
     fn vote_duration_threshold(&self) -> ChainEpoch;
 
-    fn load() -> Result<Self, Error> where Self: Sized;
+    fn voting_mode(&self) -> &VotingMode;
+
+    fn appeal_window(&self) -> ChainEpoch;
+
+    fn member_deposit(&self) -> &TokenAmount;
+
+    fn quorum(&self) -> &Quorum;
+
+    fn checkers_per_member(&self) -> usize;
+
+    fn assignment_window(&self) -> ChainEpoch;
+
+    /// Upgrades the stored state to the current layout, doing nothing
+    /// if it is up to date. Fails with `NotCaller` unless the caller is
+    /// an admin of the migrated state.
+    fn migrate() -> Result<(), Error> where Self: Sized;
+
+    /// Loads the state of `namespace`, failing if it does not exist.
+    fn load(namespace: &NamespaceId) -> Result<Self, Error> where Self: Sized;
 
+    /// Stores the state under its namespace and updates the root.
     fn save(&self) -> Result<Cid, Error>;
 }
\ No newline at end of file
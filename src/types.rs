@@ -1,65 +1,660 @@
-use fvm_shared::ActorID;
+use fvm_shared::{ActorID, MethodNum};
+use fvm_shared::address::Address;
+use fvm_shared::bigint::{bigint_ser, BigInt};
 use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use num::rational::Ratio;
 use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use crate::runtime;
+use crate::Error;
+use crate::util::resolve_address;
+use crate::p2p::{parse_multiaddr, parse_peer_id, MAX_ADDRESSES};
+use std::collections::{HashSet, VecDeque};
 
 /// The libp2p peer id representation
 pub type PeerID = String;
 /// The libp2p multi address
 pub type MultiAddr = String;
+/// The binary representation of a libp2p multi address
+pub type MultiAddrBytes = ByteBuf;
+/// Identifies one of the independent registries of the actor, e.g. `rpc`
+pub type NamespaceId = String;
+
+/// The namespace `init` creates when none is given
+pub const DEFAULT_NAMESPACE: &str = "default";
+/// Longest accepted namespace id
+pub const MAX_NAMESPACE_LEN: usize = 64;
+
+/// The addresses in the JSON params are in their string form, e.g.
+/// `f01234` or `f1...`, rather than the binary one of their serde impl.
+mod address_str {
+    use fvm_shared::address::Address;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(a: &Address, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&a.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Address, D::Error> {
+        let s = String::deserialize(d)?;
+        Address::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Same as `address_str` for lists of addresses
+mod addresses_str {
+    use fvm_shared::address::Address;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(a: &[Address], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(a.iter().map(|a| a.to_string()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Address>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .iter()
+            .map(|s| Address::from_str(s).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+/// Checks the namespace id is usable as a registry key.
+pub fn validate_namespace(namespace: &str) -> Result<(), Error> {
+    if namespace.is_empty() || namespace.len() > MAX_NAMESPACE_LEN {
+        return Err(Error::InvalidParams(format!("invalid namespace {:?}", namespace)));
+    }
+    Ok(())
+}
 
 #[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 pub struct ReportPayload {
-    pub checker: ActorID
+    pub namespace: NamespaceId,
+    /// The checker reported offline
+    pub checker: PeerID,
+    /// The caller's checker casting the vote
+    pub voter: PeerID,
+}
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct ReportMemberPayload {
+    pub namespace: NamespaceId,
+    /// The member reported offline
+    pub member: PeerID,
+    /// The caller's checker casting the vote, it must be
+    /// assigned to the member in the current window
+    pub voter: PeerID,
+}
+
+/// Upper bound on the checker scores computed by a `get_assignments`
+/// call, i.e. on the members scanned times the checkers
+pub const MAX_ASSIGNMENT_SCORES: usize = 4096;
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct AssignmentsQuery {
+    pub namespace: NamespaceId,
+    pub checker: PeerID,
+    pub epoch: ChainEpoch,
+    /// Index of the first member to scan, in the order of the registry
+    #[serde(default)]
+    pub offset: u64,
+    /// Members to scan, capped so that at most `MAX_ASSIGNMENT_SCORES`
+    /// scores are computed, the cap if not set
+    #[serde(default)]
+    pub limit: Option<u64>,
+}
+
+/// The members assigned to a checker within a range of the registry
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct AssignmentsPage {
+    pub members: Vec<PeerID>,
+    /// The offset of the next range, None once every member is scanned
+    pub next: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct CommitReportPayload {
+    pub namespace: NamespaceId,
+    /// The checker reported offline
+    pub checker: PeerID,
+    /// The caller's checker casting the vote
+    pub voter: PeerID,
+    /// See `report_commitment`
+    pub commitment: ByteBuf,
+}
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct RevealReportPayload {
+    pub namespace: NamespaceId,
+    pub checker: PeerID,
+    pub voter: PeerID,
+    /// The committed verdict, true if the checker was seen offline
+    pub offline: bool,
+    pub salt: ByteBuf,
+}
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct SubscribePayload {
+    pub namespace: NamespaceId,
+    /// The actor to notify, resolved to its ActorID
+    #[serde(with = "address_str")]
+    pub subscriber: Address,
+    /// The method of the subscriber receiving `StatusNotification`s
+    pub method: MethodNum,
+}
+
+/// Identifies a subscriber of a namespace
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct SubscriberPayload {
+    pub namespace: NamespaceId,
+    #[serde(with = "address_str")]
+    pub subscriber: Address,
+}
+
+/// Caps the notifications kept for a subscriber that failed to handle
+/// them, the oldest are dropped first.
+pub const MAX_UNDELIVERED_NOTIFICATIONS: usize = 32;
+
+/// A registered subscriber to status changes
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct Subscription {
+    pub actor: ActorID,
+    pub method: MethodNum,
+    /// The notifications the subscriber failed to handle, or that did
+    /// not fit in its gas limit, oldest first
+    #[serde(default)]
+    pub undelivered: VecDeque<StatusNotification>,
+    /// The number of undelivered notifications dropped to keep at most
+    /// `MAX_UNDELIVERED_NOTIFICATIONS`
+    #[serde(default)]
+    pub dropped: u64,
+}
+
+impl Subscription {
+    pub fn new(actor: ActorID, method: MethodNum) -> Self {
+        Self { actor, method, undelivered: VecDeque::new(), dropped: 0 }
+    }
+
+    /// Keeps a notification the subscriber did not receive, dropping the
+    /// oldest one if there are too many.
+    pub fn add_undelivered(&mut self, notification: StatusNotification) {
+        if self.undelivered.len() >= MAX_UNDELIVERED_NOTIFICATIONS {
+            self.undelivered.pop_front();
+            self.dropped += 1;
+        }
+        self.undelivered.push_back(notification);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+pub enum StatusChange {
+    /// The assigned checkers declared the member offline
+    MemberOffline,
+    /// The checker was evicted and jailed
+    CheckerJailed,
+}
+
+/// The CBOR encoded params sent to subscribers when a node status changes
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct StatusNotification {
+    /// The position of the notification in its namespace. Notifications
+    /// sent again are out of order, and a gap is a notification that was
+    /// dropped, see `Subscription::dropped`
+    pub seq: u64,
+    /// The namespace the node is registered in
+    pub namespace: NamespaceId,
+    pub id: PeerID,
+    pub change: StatusChange,
+    pub epoch: ChainEpoch,
+}
+
+/// Identifies a registered member or checker
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct NodeIdPayload {
+    pub namespace: NamespaceId,
+    pub id: PeerID,
 }
 
 #[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 pub struct NodeInfoPayload {
+    pub namespace: NamespaceId,
     id: PeerID,
     addresses: Vec<MultiAddr>,
+    #[serde(default)]
+    health_checks: Vec<HealthCheck>,
+}
+
+impl NodeInfoPayload {
+    pub fn new(
+        namespace: NamespaceId,
+        id: PeerID,
+        addresses: Vec<MultiAddr>,
+        health_checks: Vec<HealthCheck>,
+    ) -> Self {
+        Self { namespace, id, addresses, health_checks }
+    }
+}
+
+/// Maximum number of items of a batch method
+pub const MAX_BATCH_SIZE: usize = 256;
+
+/// A node of a batch, registered under the namespace of the batch
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct NodeEntry {
+    pub id: PeerID,
+    pub addresses: Vec<MultiAddr>,
+    #[serde(default)]
+    pub health_checks: Vec<HealthCheck>,
 }
 
-/// Member nodes information
 #[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct MembersBatchPayload {
+    pub namespace: NamespaceId,
+    pub members: Vec<NodeEntry>,
+}
+
+impl MembersBatchPayload {
+    /// The members as the payloads `new_member` takes
+    pub fn payloads(self) -> Vec<NodeInfoPayload> {
+        let namespace = self.namespace;
+        self.members
+            .into_iter()
+            .map(|m| NodeInfoPayload::new(namespace.clone(), m.id, m.addresses, m.health_checks))
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct CheckersReportBatchPayload {
+    pub namespace: NamespaceId,
+    /// The checkers reported offline
+    pub checkers: Vec<PeerID>,
+    /// The caller's checker casting every vote
+    pub voter: PeerID,
+}
+
+/// Checks the number of items of a batch.
+pub fn validate_batch_size(len: usize) -> Result<(), Error> {
+    if len == 0 || len > MAX_BATCH_SIZE {
+        return Err(Error::InvalidParams(format!(
+            "batch of {} items, expected 1 to {}",
+            len, MAX_BATCH_SIZE
+        )));
+    }
+    Ok(())
+}
+
+/// Offline periods kept per node to compute its uptime
+const MAX_OFFLINE_PERIODS: usize = 32;
+
+/// Strikes above this do not escalate the jail cooldown any further
+const MAX_STRIKES_ESCALATION: u32 = 16;
+
+/// Maximum number of health checks a node can register
+pub const MAX_HEALTH_CHECKS: usize = 8;
+/// Longest accepted HTTP health check path
+pub const MAX_HTTP_PATH_LEN: usize = 256;
+
+/// How checkers should probe a node. Each check targets one of the
+/// node's multiaddresses by its index in `NodeInfo::addresses`, and
+/// carries its own timeout, in milliseconds, and probing interval,
+/// in seconds.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub enum HealthCheck {
+    /// The default libp2p ping protocol
+    Libp2pPing {
+        address: u8,
+        timeout_ms: u32,
+        interval_secs: u32,
+    },
+    /// A HTTP GET query to `path`, expecting `expected_status` back
+    HttpGet {
+        address: u8,
+        path: String,
+        expected_status: u16,
+        timeout_ms: u32,
+        interval_secs: u32,
+    },
+    /// Opening a TCP connection
+    TcpConnect {
+        address: u8,
+        timeout_ms: u32,
+        interval_secs: u32,
+    },
+    /// A QUIC handshake
+    QuicPing {
+        address: u8,
+        timeout_ms: u32,
+        interval_secs: u32,
+    },
+}
+
+impl HealthCheck {
+    /// The index of the probed address in `NodeInfo::addresses`
+    pub fn address(&self) -> u8 {
+        match self {
+            HealthCheck::Libp2pPing { address, .. }
+            | HealthCheck::HttpGet { address, .. }
+            | HealthCheck::TcpConnect { address, .. }
+            | HealthCheck::QuicPing { address, .. } => *address,
+        }
+    }
+
+    pub fn timeout_ms(&self) -> u32 {
+        match self {
+            HealthCheck::Libp2pPing { timeout_ms, .. }
+            | HealthCheck::HttpGet { timeout_ms, .. }
+            | HealthCheck::TcpConnect { timeout_ms, .. }
+            | HealthCheck::QuicPing { timeout_ms, .. } => *timeout_ms,
+        }
+    }
+
+    pub fn interval_secs(&self) -> u32 {
+        match self {
+            HealthCheck::Libp2pPing { interval_secs, .. }
+            | HealthCheck::HttpGet { interval_secs, .. }
+            | HealthCheck::TcpConnect { interval_secs, .. }
+            | HealthCheck::QuicPing { interval_secs, .. } => *interval_secs,
+        }
+    }
+
+    /// Checks the probe is usable against a node with `total_addresses`.
+    fn validate(&self, total_addresses: usize) -> Result<(), Error> {
+        if self.address() as usize >= total_addresses {
+            return Err(Error::InvalidHealthCheck(String::from("unknown address")));
+        }
+        if self.timeout_ms() == 0 || self.interval_secs() == 0 {
+            return Err(Error::InvalidHealthCheck(String::from("zero timeout or interval")));
+        }
+        if let HealthCheck::HttpGet { path, expected_status, .. } = self {
+            if !path.starts_with('/') || path.len() > MAX_HTTP_PATH_LEN {
+                return Err(Error::InvalidHealthCheck(format!("invalid path {:?}", path)));
+            }
+            if !(100..=599).contains(expected_status) {
+                return Err(Error::InvalidHealthCheck(format!("invalid status {}", expected_status)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The registry a node belongs to
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+pub enum NodeKind {
+    Checker,
+    Member,
+}
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct TransferOwnershipPayload {
+    pub namespace: NamespaceId,
+    pub id: PeerID,
+    pub kind: NodeKind,
+    /// Resolved to its ActorID when the transfer is made
+    #[serde(with = "address_str")]
+    pub new_owner: Address,
+    /// If set, the transfer only takes effect once `new_owner`
+    /// calls `accept_ownership`.
+    #[serde(default)]
+    pub require_accept: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct AcceptOwnershipPayload {
+    pub namespace: NamespaceId,
+    pub id: PeerID,
+    pub kind: NodeKind,
+}
+
+/// Whether a node takes part in the registry
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub enum NodeStatus {
+    #[default]
+    Active,
+    /// Evicted checker, it can call `unjail` from `until_epoch` on.
+    /// `strikes` counts the times it was jailed.
+    Jailed {
+        until_epoch: ChainEpoch,
+        strikes: u32,
+    },
+}
+
+/// Member nodes information
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub struct NodeInfo {
     /// PeerID of the node
     id: PeerID,
     /// The creator of the node. Only creator can modifier other fields of this struct
     creator: ActorID,
-    /// List of multiaddresses exposed by the node
-    /// along with the supported healthcheck endpoints.
+    /// List of multiaddresses exposed by the node, in their
+    /// binary form. At most `MAX_ADDRESSES` are accepted.
     ///
-    /// e.g. [ /ip4/10.1.1.1/quic/8080/p2p/<peer_id>/ping,
-    ///        /ip4/10.1.1.1/tcp/8081/http/get/healtcheck,
+    /// e.g. [ /ip4/10.1.1.1/udp/8080/quic/p2p/<peer_id>,
+    ///        /ip4/10.1.1.1/tcp/8081/http,
     ///      ]
-    /// These multiaddresses are signalling that the liveliness
-    /// can be checked by using the default libp2p ping protocol
-    /// in the first multiaddress, or by sending a GET HTTP
-    /// query to the /healtchek endpoint at 10.1.1.1:8081.
-    addresses: Vec<MultiAddr>,
+    addresses: Vec<MultiAddrBytes>,
+    /// How the liveliness of the node is checked, e.g. a
+    /// `Libp2pPing` on address 0 and a `HttpGet` of
+    /// `/healthcheck` on address 1.
+    #[serde(default)]
+    health_checks: Vec<HealthCheck>,
+    /// The owner a two-step transfer is waiting on, if any
+    #[serde(default)]
+    pending_owner: Option<ActorID>,
+    /// Epoch at which the assigned checkers declared the member
+    /// offline. Cleared when the owner edits the node.
+    #[serde(default)]
+    offline_since: Option<ChainEpoch>,
+    /// Whether the node is active or jailed
+    #[serde(default)]
+    status: NodeStatus,
+    /// The number of times the node was jailed, kept after unjailing
+    /// to escalate the next cooldown
+    #[serde(default)]
+    strikes: u32,
+    /// The deposit paid to register the node, refunded on removal
+    #[serde(default, with = "bigint_ser")]
+    deposit: TokenAmount,
+    /// Epoch the node was registered at
+    #[serde(default)]
+    registered_at: ChainEpoch,
+    /// The last `MAX_OFFLINE_PERIODS` closed offline periods, as
+    /// `(offline_since, back_online)` epochs
+    #[serde(default)]
+    offline_periods: Vec<(ChainEpoch, ChainEpoch)>,
 }
 
-impl From<NodeInfoPayload> for NodeInfo {
-    fn from(p: NodeInfoPayload) -> Self {
-        NodeInfo {
-            creator: fvm_sdk::message::caller(),
-            id: p.id,
-            addresses: p.addresses
-        }
+impl TryFrom<NodeInfoPayload> for NodeInfo {
+    type Error = Error;
+
+    fn try_from(p: NodeInfoPayload) -> Result<Self, Self::Error> {
+        NodeInfo::new(p.id, runtime::caller(), p.addresses, p.health_checks)
     }
 }
 
 impl NodeInfo {
+    pub fn id(&self) -> &PeerID {
+        &self.id
+    }
+
     pub fn creator(&self) -> &ActorID {
         &self.creator
     }
 
-    pub fn new(id: PeerID, creator: ActorID, addresses: Vec<MultiAddr>) -> Self {
+    pub fn pending_owner(&self) -> &Option<ActorID> {
+        &self.pending_owner
+    }
+
+    /// Creates the node, validating its PeerID, multiaddresses and
+    /// health checks.
+    pub fn new(
+        id: PeerID,
+        creator: ActorID,
+        addresses: Vec<MultiAddr>,
+        health_checks: Vec<HealthCheck>,
+    ) -> Result<Self, Error> {
+        parse_peer_id(&id)?;
+        if addresses.len() > MAX_ADDRESSES {
+            return Err(Error::TooManyAddresses);
+        }
+        let addresses = addresses
+            .iter()
+            .map(|a| parse_multiaddr(a).map(ByteBuf::from))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if health_checks.len() > MAX_HEALTH_CHECKS {
+            return Err(Error::InvalidHealthCheck(String::from("too many health checks")));
+        }
+        for c in health_checks.iter() {
+            c.validate(addresses.len())?;
+        }
+
+        Ok(Self {
+            id,
+            creator,
+            addresses,
+            health_checks,
+            pending_owner: None,
+            offline_since: None,
+            status: NodeStatus::Active,
+            strikes: 0,
+            deposit: TokenAmount::default(),
+            registered_at: runtime::curr_epoch(),
+            offline_periods: vec![],
+        })
+    }
+
+    /// Converts a node of the version 0 state, registered at `epoch`.
+    /// Unlike `new` nothing is rejected, so existing registrations
+    /// survive the migration, but the addresses that do not parse are
+    /// dropped.
+    pub fn from_v0(id: PeerID, creator: ActorID, addresses: &[MultiAddr], epoch: ChainEpoch) -> Self {
+        let addresses = addresses
+            .iter()
+            .filter_map(|a| parse_multiaddr(a).ok().map(ByteBuf::from))
+            .take(MAX_ADDRESSES)
+            .collect();
+
         Self {
             id,
             creator,
             addresses,
+            health_checks: vec![],
+            pending_owner: None,
+            offline_since: None,
+            status: NodeStatus::Active,
+            strikes: 0,
+            deposit: TokenAmount::default(),
+            registered_at: epoch,
+            offline_periods: vec![],
+        }
+    }
+
+    /// Applies the editable fields of `other` to the node, keeping its
+    /// ownership state, and clears any offline verdict at `epoch`. The
+    /// owner fixing its node is trusted to bring it back online, the
+    /// checkers declare it offline again otherwise.
+    pub fn apply_edit(&mut self, other: NodeInfo, epoch: ChainEpoch) {
+        self.addresses = other.addresses;
+        self.health_checks = other.health_checks;
+        if let Some(since) = self.offline_since.take() {
+            if self.offline_periods.len() >= MAX_OFFLINE_PERIODS {
+                self.offline_periods.remove(0);
+            }
+            self.offline_periods.push((since, epoch));
         }
     }
+
+    /// Returns the number of epochs in `[from, to)` the node was registered,
+    /// and how many of those it was not declared offline. Offline periods
+    /// older than the last `MAX_OFFLINE_PERIODS` are not accounted for.
+    pub fn uptime(&self, from: ChainEpoch, to: ChainEpoch) -> (ChainEpoch, ChainEpoch) {
+        let start = from.max(self.registered_at);
+        if start >= to {
+            return (0, 0);
+        }
+
+        let current = self.offline_since.map(|since| (since, to));
+        let offline: ChainEpoch = self.offline_periods
+            .iter()
+            .copied()
+            .chain(current)
+            .map(|(a, b)| (b.min(to) - a.max(start)).max(0))
+            .sum();
+
+        let tracked = to - start;
+        (tracked, tracked - offline)
+    }
+
+    pub fn offline_since(&self) -> &Option<ChainEpoch> {
+        &self.offline_since
+    }
+
+    /// Flags the node offline from `epoch`, unless it already is: the
+    /// period starts at the first verdict.
+    pub fn set_offline(&mut self, epoch: ChainEpoch) {
+        self.offline_since.get_or_insert(epoch);
+    }
+
+    pub fn deposit(&self) -> &TokenAmount {
+        &self.deposit
+    }
+
+    pub fn set_deposit(&mut self, deposit: TokenAmount) {
+        self.deposit = deposit;
+    }
+
+    #[cfg(feature = "client")]
+    pub fn status(&self) -> &NodeStatus {
+        &self.status
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == NodeStatus::Active
+    }
+
+    pub fn strikes(&self) -> u32 {
+        self.strikes
+    }
+
+    /// Jails the node until `epoch + cooldown * 2^strikes`, so every
+    /// new strike doubles the cooldown.
+    pub fn jail(&mut self, epoch: ChainEpoch, cooldown: ChainEpoch) {
+        let factor = 1i64 << self.strikes.min(MAX_STRIKES_ESCALATION);
+        self.strikes += 1;
+        self.status = NodeStatus::Jailed {
+            until_epoch: epoch + cooldown.saturating_mul(factor),
+            strikes: self.strikes,
+        };
+    }
+
+    pub fn unjail(&mut self, epoch: ChainEpoch) -> Result<(), Error> {
+        match self.status {
+            NodeStatus::Jailed { until_epoch, .. } if until_epoch <= epoch => {
+                self.status = NodeStatus::Active;
+                Ok(())
+            }
+            NodeStatus::Jailed { .. } => Err(Error::Jailed),
+            NodeStatus::Active => Err(Error::NotExists),
+        }
+    }
+
+    #[cfg(feature = "client")]
+    pub fn health_checks(&self) -> &[HealthCheck] {
+        &self.health_checks
+    }
+
+    /// Hands the node over to `new_owner`, clearing any pending transfer.
+    pub fn set_owner(&mut self, new_owner: ActorID) {
+        self.creator = new_owner;
+        self.pending_owner = None;
+    }
+
+    pub fn set_pending_owner(&mut self, new_owner: ActorID) {
+        self.pending_owner = Some(new_owner);
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
@@ -68,7 +663,7 @@ pub struct Votes {
     /// checker.
     pub last_vote: ChainEpoch,
     /// Checkers that have voted
-    pub votes: Vec<ActorID>,
+    pub votes: Vec<PeerID>,
 }
 
 impl Votes {
@@ -76,16 +671,18 @@ impl Votes {
         Self { last_vote: epoch, votes: vec![] }
     }
 
-    pub fn has_voted(&self, p: &ActorID) -> bool {
+    pub fn has_voted(&self, p: &PeerID) -> bool {
         self.votes.contains(p)
     }
 
+    /// Whether the round is still open at `epoch`, i.e. the last
+    /// vote is not older than `threshold` epochs.
     pub fn within_threshold(&self, epoch: ChainEpoch, threshold: ChainEpoch) -> bool {
-        self.last_vote + threshold < epoch
+        epoch <= self.last_vote + threshold
     }
 
-    pub fn vote(&mut self, p: &ActorID) {
-        self.votes.push(*p)
+    pub fn vote(&mut self, p: &PeerID) {
+        self.votes.push(p.clone())
     }
 
     pub fn total_votes(&self) -> usize {
@@ -93,11 +690,479 @@ impl Votes {
     }
 }
 
-/// Constructor parameters
-#[derive(Deserialize)]
+/// The fraction of checkers that must vote a checker offline before it is
+/// removed, stored as a reduced `numerator/denominator` rational.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+pub struct Quorum {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl Default for Quorum {
+    /// Strictly more than 2/3 of the checkers.
+    fn default() -> Self {
+        Self { numerator: 2, denominator: 3 }
+    }
+}
+
+impl Quorum {
+    /// Creates a new quorum. The denominator must be non-zero and the ratio
+    /// must be below one: the votes must be strictly above the ratio, so
+    /// not even all of the checkers could reach a ratio of one.
+    pub fn new(numerator: u64, denominator: u64) -> Result<Self, Error> {
+        if denominator == 0 || numerator >= denominator {
+            return Err(Error::InvalidQuorum);
+        }
+        let r = Ratio::new(numerator, denominator);
+        Ok(Self { numerator: *r.numer(), denominator: *r.denom() })
+    }
+
+    /// Checks whether `votes` out of `total` are enough to contest the
+    /// quorum, i.e. `votes / total >= 1 - numerator / denominator`, so the
+    /// remaining voters cannot be strictly above the quorum any more.
+    pub fn is_contested(&self, votes: usize, total: usize) -> bool {
+        let complement = (self.denominator - self.numerator) as u128;
+        (votes as u128) * (self.denominator as u128) >= (total as u128) * complement
+    }
+
+    /// Checks whether `votes` out of `total` is strictly above the quorum, i.e.
+    /// `votes / total > numerator / denominator`. The comparison is done on the
+    /// cross products `votes * denominator > total * numerator` widened to
+    /// u128, so there is neither truncation nor overflow, even on wasm32.
+    pub fn is_reached(&self, votes: usize, total: usize) -> bool {
+        (votes as u128) * (self.denominator as u128) > (total as u128) * (self.numerator as u128)
+    }
+}
+
+/// How offline checkers are voted
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub enum VotingMode {
+    /// Votes are public and counted as soon as they are cast
+    #[default]
+    Instant,
+    /// Checkers first commit to a hidden verdict for `commit_duration`
+    /// epochs, then open it during the next `reveal_duration` epochs
+    CommitReveal {
+        commit_duration: ChainEpoch,
+        reveal_duration: ChainEpoch,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VotePhase {
+    Commit,
+    Reveal,
+    Over,
+}
+
+/// The commitment `voter` submits for a verdict on `target`:
+/// `blake2b_256(report_commitment_preimage(target, voter, offline, salt))`.
+/// It binds the voter, so that a voter cannot copy the commitment of
+/// another one and replay its reveal.
+pub fn report_commitment(target: &PeerID, voter: &PeerID, offline: bool, salt: &[u8]) -> [u8; 32] {
+    runtime::hash_blake2b(&report_commitment_preimage(target, voter, offline, salt))
+}
+
+/// `len(target) as u64 big endian || target || len(voter) as u64 big endian
+/// || voter || offline as u8 || salt`
+pub fn report_commitment_preimage(target: &PeerID, voter: &PeerID, offline: bool, salt: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(target.len() + voter.len() + salt.len() + 17);
+    data.extend_from_slice(&(target.len() as u64).to_be_bytes());
+    data.extend_from_slice(target.as_bytes());
+    data.extend_from_slice(&(voter.len() as u64).to_be_bytes());
+    data.extend_from_slice(voter.as_bytes());
+    data.push(offline as u8);
+    data.extend_from_slice(salt);
+    data
+}
+
+/// A commit-reveal voting round on an offline checker
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct CommitRound {
+    /// Epoch of the first commitment, the commit phase starts there
+    pub start: ChainEpoch,
+    /// Commitments per voter
+    pub commits: Vec<(PeerID, ByteBuf)>,
+    /// Opened verdicts per voter, true meaning offline
+    pub reveals: Vec<(PeerID, bool)>,
+}
+
+impl CommitRound {
+    pub fn new(start: ChainEpoch) -> Self {
+        Self { start, commits: vec![], reveals: vec![] }
+    }
+
+    pub fn phase(&self, epoch: ChainEpoch, commit_duration: ChainEpoch, reveal_duration: ChainEpoch) -> VotePhase {
+        if epoch < self.start + commit_duration {
+            VotePhase::Commit
+        } else if epoch < self.start + commit_duration + reveal_duration {
+            VotePhase::Reveal
+        } else {
+            VotePhase::Over
+        }
+    }
+
+    pub fn commit(&mut self, voter: &PeerID, commitment: ByteBuf) -> Result<(), Error> {
+        if self.commits.iter().any(|(v, _)| v == voter) {
+            return Err(Error::AlreadyVoted(voter.clone()));
+        }
+        self.commits.push((voter.clone(), commitment));
+        Ok(())
+    }
+
+    /// Opens the commitment of `voter`, checking it matches the verdict.
+    pub fn reveal(&mut self, target: &PeerID, voter: &PeerID, offline: bool, salt: &[u8]) -> Result<(), Error> {
+        if self.reveals.iter().any(|(v, _)| v == voter) {
+            return Err(Error::AlreadyVoted(voter.clone()));
+        }
+        let (_, commitment) = self.commits
+            .iter()
+            .find(|(v, _)| v == voter)
+            .ok_or(Error::NotExists)?;
+        if commitment.as_slice() != report_commitment(target, voter, offline, salt) {
+            return Err(Error::InvalidCommitment);
+        }
+        self.reveals.push((voter.clone(), offline));
+        Ok(())
+    }
+
+    pub fn all_revealed(&self) -> bool {
+        self.reveals.len() == self.commits.len()
+    }
+
+    /// Returns the number of offline verdicts and of revealed votes.
+    pub fn tally(&self) -> (usize, usize) {
+        let offline = self.reveals.iter().filter(|(_, o)| *o).count();
+        (offline, self.reveals.len())
+    }
+}
+
+/// A checker voted offline, waiting for the appeal window to close
+/// before it is evicted.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct PendingEviction {
+    /// Epoch the quorum was reached at, the appeal window starts there
+    pub since: ChainEpoch,
+    /// Whether the accused checker filed a counter-claim
+    pub appealed: bool,
+    /// Checkers that saw the accused online
+    pub online_votes: Vec<PeerID>,
+}
+
+impl PendingEviction {
+    pub fn new(since: ChainEpoch) -> Self {
+        Self { since, appealed: false, online_votes: vec![] }
+    }
+
+    pub fn is_open(&self, epoch: ChainEpoch, appeal_window: ChainEpoch) -> bool {
+        epoch < self.since + appeal_window
+    }
+
+    pub fn vouch(&mut self, voter: &PeerID) -> Result<(), Error> {
+        if self.online_votes.contains(voter) {
+            return Err(Error::AlreadyVoted(voter.clone()));
+        }
+        self.online_votes.push(voter.clone());
+        Ok(())
+    }
+
+    /// The appeal succeeds if the accused filed a counter-claim and enough
+    /// checkers saw it online to contest the offline quorum.
+    pub fn appeal_succeeds(&self, quorum: &Quorum, total_checkers: usize) -> bool {
+        self.appealed && quorum.is_contested(self.online_votes.len(), total_checkers)
+    }
+}
+
+/// Number of checkers assigned to each member if not configured
+pub const DEFAULT_CHECKERS_PER_MEMBER: u64 = 3;
+
+/// The bit width of the HAMTs of a namespace, unless set at init
+pub const DEFAULT_HAMT_BIT_WIDTH: u32 = 5;
+
+/// HAMT nodes have a 256 bit bitfield, i.e. at most 2^8 children
+pub const MAX_HAMT_BIT_WIDTH: u32 = 8;
+
+/// Constructor parameters, also used to create new namespaces
+#[derive(Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
 pub struct InitParams {
+    /// The namespace to create, `DEFAULT_NAMESPACE` if not set
+    #[serde(default = "default_namespace")]
+    pub namespace: NamespaceId,
     pub ids: Vec<String>,
-    pub creators: Vec<ActorID>,
+    /// The owners of the initial checkers, resolved to their ActorIDs
+    #[serde(with = "addresses_str")]
+    pub creators: Vec<Address>,
     pub addresses: Vec<Vec<String>>,
+    #[serde(default)]
+    pub health_checks: Vec<Vec<HealthCheck>>,
+    /// Number of checkers assigned to each member
+    pub checkers_per_member: Option<u64>,
+    /// Length, in epochs, of the windows checkers are reassigned in
+    pub assignment_window: Option<ChainEpoch>,
     pub voting_duration: Option<ChainEpoch>,
+    #[serde(default)]
+    pub voting_mode: VotingMode,
+    /// Epochs an offline checker has to appeal before it is evicted
+    pub appeal_window: Option<ChainEpoch>,
+    /// Epochs an evicted checker stays jailed on its first strike
+    pub jail_cooldown: Option<ChainEpoch>,
+    /// Refundable deposit to register a member, in attoFIL as a
+    /// decimal string
+    pub member_deposit: Option<String>,
+    /// Cap on the total number of members
+    pub max_members: Option<u64>,
+    /// Cap on the number of members a single creator can register
+    pub max_members_per_creator: Option<u64>,
+    pub quorum: Option<Quorum>,
+    /// Bit width of the HAMTs of the namespace, from 1 to
+    /// `MAX_HAMT_BIT_WIDTH`. Wider nodes make shallower trees, cheaper
+    /// to read but more expensive to write.
+    pub hamt_bit_width: Option<u32>,
+}
+
+fn default_namespace() -> NamespaceId {
+    NamespaceId::from(DEFAULT_NAMESPACE)
+}
+
+impl InitParams {
+    /// Checks the initial checkers are well formed: one creator and one
+    /// list of addresses per valid PeerID, no PeerID listed twice, and at
+    /// least `checkers_per_member` of them so that every member can be
+    /// fully assigned from the start. See `creator_ids` for the creators.
+    pub fn validate(&self) -> Result<(), Error> {
+        let len = self.ids.len();
+        if self.creators.len() != len || self.addresses.len() != len {
+            return Err(Error::InvalidParams(format!(
+                "{} ids but {} creators and {} address lists",
+                len,
+                self.creators.len(),
+                self.addresses.len()
+            )));
+        }
+        if !self.health_checks.is_empty() && self.health_checks.len() != len {
+            return Err(Error::InvalidParams(format!(
+                "{} ids but {} health check lists",
+                len,
+                self.health_checks.len()
+            )));
+        }
+
+        let min = self.checkers_per_member.unwrap_or(DEFAULT_CHECKERS_PER_MEMBER);
+        if (len as u64) < min {
+            return Err(Error::InvalidParams(format!(
+                "{} initial checkers, at least checkers_per_member ({}) are needed",
+                len, min
+            )));
+        }
+
+        let mut ids = HashSet::new();
+        for id in self.ids.iter() {
+            parse_peer_id(id)?;
+            if !ids.insert(id) {
+                return Err(Error::InvalidParams(format!("duplicate PeerID {:?}", id)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves the creators to their ActorIDs, failing if one does not
+    /// exist or if two addresses point to the same actor.
+    pub fn creator_ids(&self) -> Result<Vec<ActorID>, Error> {
+        let mut creators = HashSet::new();
+        let mut ids = vec![];
+        for c in self.creators.iter() {
+            let id = resolve_address(c)?;
+            if !creators.insert(id) {
+                return Err(Error::InvalidParams(format!("duplicate creator {}", c)));
+            }
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    /// Parses the member deposit, defaulting to no deposit.
+    pub fn member_deposit(&self) -> Result<TokenAmount, Error> {
+        match &self.member_deposit {
+            None => Ok(TokenAmount::default()),
+            Some(d) => {
+                let atto = d
+                    .parse::<BigInt>()
+                    .map_err(|_| Error::InvalidParams(format!("invalid member_deposit {:?}", d)))?;
+                if atto < BigInt::from(0) {
+                    return Err(Error::InvalidParams(String::from("member_deposit must not be negative")));
+                }
+                Ok(atto)
+            }
+        }
+    }
+
+    /// The HAMT bit width, defaulting to `DEFAULT_HAMT_BIT_WIDTH`.
+    pub fn hamt_bit_width(&self) -> Result<u32, Error> {
+        let width = self.hamt_bit_width.unwrap_or(DEFAULT_HAMT_BIT_WIDTH);
+        if width == 0 || width > MAX_HAMT_BIT_WIDTH {
+            return Err(Error::InvalidParams(format!(
+                "hamt_bit_width must be from 1 to {}",
+                MAX_HAMT_BIT_WIDTH
+            )));
+        }
+        Ok(width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::rational::Ratio;
+
+    /// `votes / total > numerator / denominator`, computed on exact rationals
+    fn reached(q: &Quorum, votes: usize, total: usize) -> bool {
+        Ratio::new(votes as u128, total as u128) > Ratio::new(q.numerator as u128, q.denominator as u128)
+    }
+
+    /// `votes / total >= 1 - numerator / denominator`, on exact rationals
+    fn contested(q: &Quorum, votes: usize, total: usize) -> bool {
+        Ratio::new(votes as u128, total as u128) >= Ratio::new(1, 1) - Ratio::new(q.numerator as u128, q.denominator as u128)
+    }
+
+    fn quorums() -> Vec<Quorum> {
+        let mut quorums = vec![];
+        for d in 1..=12 {
+            for n in 0..d {
+                quorums.push(Quorum::new(n, d).unwrap());
+            }
+        }
+        quorums.push(Quorum::new(u64::MAX - 1, u64::MAX).unwrap());
+        quorums.push(Quorum::new(1, u64::MAX).unwrap());
+        quorums
+    }
+
+    #[test]
+    fn quorum_rejects_unreachable_ratios() {
+        for (n, d) in [(1, 0), (3, 3), (4, 3), (u64::MAX, u64::MAX)] {
+            assert!(matches!(Quorum::new(n, d), Err(Error::InvalidQuorum)), "{}/{}", n, d);
+        }
+        assert_eq!(Quorum::new(4, 6).unwrap(), Quorum { numerator: 2, denominator: 3 });
+    }
+
+    #[test]
+    fn quorum_matches_exact_ratio_on_small_totals() {
+        for q in quorums() {
+            for total in 1..=64 {
+                for votes in 0..=total {
+                    assert_eq!(q.is_reached(votes, total), reached(&q, votes, total), "{:?} {}/{}", q, votes, total);
+                    assert_eq!(q.is_contested(votes, total), contested(&q, votes, total), "{:?} {}/{}", q, votes, total);
+                }
+                // every valid quorum is reached by all of the checkers
+                assert!(q.is_reached(total, total), "{:?} {}", q, total);
+            }
+        }
+    }
+
+    #[test]
+    fn quorum_matches_exact_ratio_on_large_totals() {
+        let totals = [usize::MAX, usize::MAX - 1, usize::MAX / 3, 1 << 40, 1_000_003];
+        for q in quorums() {
+            for total in totals {
+                let threshold = (total as u128 * q.numerator as u128 / q.denominator as u128) as usize;
+                for votes in [0, 1, threshold.saturating_sub(1), threshold, threshold.saturating_add(1), total - 1, total] {
+                    let votes = votes.min(total);
+                    assert_eq!(q.is_reached(votes, total), reached(&q, votes, total), "{:?} {}/{}", q, votes, total);
+                    assert_eq!(q.is_contested(votes, total), contested(&q, votes, total), "{:?} {}/{}", q, votes, total);
+                }
+            }
+        }
+    }
+
+    fn http_get(path: &str, expected_status: u16) -> HealthCheck {
+        HealthCheck::HttpGet { address: 0, path: path.to_string(), expected_status, timeout_ms: 1000, interval_secs: 60 }
+    }
+
+    #[test]
+    fn health_checks_accept_well_formed_probes() {
+        let longest = format!("/{}", "a".repeat(MAX_HTTP_PATH_LEN - 1));
+        for check in [
+            HealthCheck::Libp2pPing { address: 0, timeout_ms: 1, interval_secs: 1 },
+            HealthCheck::TcpConnect { address: 1, timeout_ms: 1000, interval_secs: 60 },
+            HealthCheck::QuicPing { address: 1, timeout_ms: u32::MAX, interval_secs: u32::MAX },
+            http_get("/", 100),
+            http_get("/health?full=1", 200),
+            http_get(&longest, 599),
+        ] {
+            assert!(check.validate(2).is_ok(), "{:?}", check);
+        }
+    }
+
+    #[test]
+    fn health_checks_reject_malformed_probes() {
+        let too_long = format!("/{}", "a".repeat(MAX_HTTP_PATH_LEN));
+        for (check, total_addresses) in [
+            // the probed address must exist
+            (HealthCheck::Libp2pPing { address: 0, timeout_ms: 1000, interval_secs: 60 }, 0),
+            (HealthCheck::TcpConnect { address: 2, timeout_ms: 1000, interval_secs: 60 }, 2),
+            (HealthCheck::QuicPing { address: u8::MAX, timeout_ms: 1000, interval_secs: 60 }, 16),
+            // timeouts and intervals must be set
+            (HealthCheck::Libp2pPing { address: 0, timeout_ms: 0, interval_secs: 60 }, 1),
+            (HealthCheck::TcpConnect { address: 0, timeout_ms: 1000, interval_secs: 0 }, 1),
+            (HealthCheck::HttpGet { address: 0, path: "/".into(), expected_status: 200, timeout_ms: 0, interval_secs: 0 }, 1),
+            // paths must be absolute and bounded
+            (http_get("", 200), 1),
+            (http_get("health", 200), 1),
+            (http_get(&too_long, 200), 1),
+            // statuses must be in the HTTP range
+            (http_get("/", 0), 1),
+            (http_get("/", 99), 1),
+            (http_get("/", 600), 1),
+        ] {
+            assert!(
+                matches!(check.validate(total_addresses), Err(Error::InvalidHealthCheck(_))),
+                "{:?} on {} addresses",
+                check,
+                total_addresses
+            );
+        }
+    }
+
+    #[test]
+    fn commit_rounds_move_through_their_phases() {
+        let round = CommitRound::new(100);
+        for (epoch, phase) in [(100, VotePhase::Commit), (109, VotePhase::Commit), (110, VotePhase::Reveal), (114, VotePhase::Reveal), (115, VotePhase::Over)] {
+            assert_eq!(round.phase(epoch, 10, 5), phase, "{}", epoch);
+        }
+    }
+
+    #[test]
+    fn commit_rounds_only_open_matching_commitments() {
+        let (target, alice, bob) = (String::from("target"), String::from("alice"), String::from("bob"));
+        let commitment = |voter: &PeerID, offline, salt: &[u8]| ByteBuf::from(report_commitment(&target, voter, offline, salt).to_vec());
+        assert_ne!(commitment(&alice, true, b"salt"), commitment(&bob, true, b"salt"));
+
+        let mut round = CommitRound::new(0);
+        round.commit(&alice, commitment(&alice, true, b"salt")).unwrap();
+        assert!(matches!(round.commit(&alice, commitment(&alice, false, b"salt")), Err(Error::AlreadyVoted(_))));
+        // bob replays the commitment of alice
+        round.commit(&bob, commitment(&alice, true, b"salt")).unwrap();
+
+        assert!(matches!(round.reveal(&target, &alice, true, b"pepper"), Err(Error::InvalidCommitment)));
+        assert!(matches!(round.reveal(&target, &alice, false, b"salt"), Err(Error::InvalidCommitment)));
+        assert!(matches!(round.reveal(&String::from("other"), &alice, true, b"salt"), Err(Error::InvalidCommitment)));
+        assert!(matches!(round.reveal(&target, &bob, true, b"salt"), Err(Error::InvalidCommitment)));
+        assert!(matches!(round.reveal(&target, &String::from("carol"), true, b"salt"), Err(Error::NotExists)));
+
+        round.reveal(&target, &alice, true, b"salt").unwrap();
+        assert!(matches!(round.reveal(&target, &alice, true, b"salt"), Err(Error::AlreadyVoted(_))));
+        assert!(!round.all_revealed());
+        assert_eq!(round.tally(), (1, 1));
+    }
+
+    #[test]
+    fn subscriptions_count_the_undelivered_notifications_they_drop() {
+        let mut subscription = Subscription::new(300, 2);
+        for seq in 0..MAX_UNDELIVERED_NOTIFICATIONS as u64 + 3 {
+            let id = String::from("member");
+            subscription.add_undelivered(StatusNotification { seq, namespace: NamespaceId::new(), id, change: StatusChange::MemberOffline, epoch: 0 });
+        }
+        assert_eq!(subscription.dropped, 3);
+        assert_eq!(subscription.undelivered.len(), MAX_UNDELIVERED_NOTIFICATIONS);
+        assert_eq!(subscription.undelivered.front().map(|n| n.seq), Some(3));
+    }
 }
@@ -1,15 +1,42 @@
+use crate::runtime;
 use crate::Error;
+use fvm_ipld_encoding::DAG_CBOR;
+use fvm_shared::address::Address;
+use fvm_shared::ActorID;
+use serde::Serialize;
 
 pub fn parse_params_or_abort<'a, T: serde::Deserialize<'a>>(params: &'a [u8]) -> T {
     match serde_json::from_slice::<T>(params) {
         Ok(t) => t,
         Err(e) => fvm_sdk::vm::abort(
             Error::CannotDeserialize.code(),
-            Option::from(e.to_string().as_str()),
+            Option::from(format!("{}: {}", Error::CannotDeserialize, e).as_str()),
         ),
     }
 }
 
+/// Parses CBOR encoded params, used by the methods other actors call.
+pub fn parse_cbor_params_or_abort<T: serde::de::DeserializeOwned>(params: &[u8]) -> T {
+    match fvm_ipld_encoding::from_slice::<T>(params) {
+        Ok(t) => t,
+        Err(e) => fvm_sdk::vm::abort(
+            Error::CannotDeserialize.code(),
+            Option::from(format!("{}: {}", Error::CannotDeserialize, e).as_str()),
+        ),
+    }
+}
+
+/// Stores the CBOR encoded return value of a method and returns its block id.
+pub fn return_cbor<T: Serialize>(v: T) -> Result<u32, Error> {
+    let bytes = fvm_ipld_encoding::to_vec(&v)?;
+    Ok(fvm_sdk::ipld::put_block(DAG_CBOR, &bytes)?)
+}
+
+/// Resolves an address to the ActorID of the actor holding it.
+pub fn resolve_address(address: &Address) -> Result<ActorID, Error> {
+    runtime::resolve_address(address).ok_or(Error::UnresolvedAddress(*address))
+}
+
 #[macro_export]
 macro_rules! ensure {
     ( $x:expr, $y:expr ) => {{